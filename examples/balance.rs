@@ -0,0 +1,50 @@
+// Plays a bunch of games on every difficulty with a player that never moves, and prints how long
+// they lasted. Useful for seeing what changing the difficulty tables does. Given a difficulty code
+// from the custom difficulty editor, only that difficulty is played.
+//
+//     cargo run --release --example balance -- [games per difficulty] [max ticks] [code]
+
+extern crate soviet_sim;
+
+use soviet_sim::sim::{Simulation, Outcome};
+use soviet_sim::difficulty::Difficulty;
+
+use std::env;
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let number = |idx: usize, default: u64| {
+        args.get(idx).map(|x| x.parse::<u64>().expect("Arguments should be numbers")).unwrap_or(default)
+    };
+    let games = number(0, 20);
+    let max_ticks = number(1, 60 * 60 * 5);
+    let custom = args.get(2).map(|code| Difficulty::from_code(code).unwrap_or_else(|e| panic!("Bad code: {}", e)));
+
+    let mut difficulty = custom.unwrap_or(Difficulty::EASY);
+    loop {
+        let mut died = 0;
+        let mut total_ticks = 0;
+        let mut total_police = 0;
+
+        for seed in 0..games {
+            let mut sim = Simulation::new(difficulty, seed as u32);
+            if sim.step(max_ticks) == Some(Outcome::Died) {
+                died += 1;
+            }
+            total_ticks += sim.ticks();
+            total_police += sim.police_count();
+        }
+
+        println!(
+            "{:<16} died in {:>3}/{} games, survived {:>6} ticks and saw {:>4} police on average",
+            difficulty.to_string(),
+            died, games,
+            total_ticks / games,
+            total_police as u64 / games);
+
+        if difficulty == difficulty.harder() {
+            break;
+        }
+        difficulty = difficulty.harder();
+    }
+}
@@ -0,0 +1,399 @@
+// Plays the game in a terminal, drawing with ANSI truecolor escapes.
+//
+// Terminals only tell us when a key is typed, never when it's released, so every key is pressed
+// and released right away. Letters that are used as modifiers in the controls (like M for mining)
+// are instead held down until the next key, so typing M and then an arrow mines.
+//
+// Extra blocks, recipes and loot tables can be loaded with --blocks, --recipes and --loot, see
+// the files in defs/. Key bindings are kept in the file given with --bindings, and the high
+// scores in the directory given with --store, one file each. Without it they only last until
+// quitting.
+
+extern crate soviet_sim;
+
+use soviet_sim::host::{self, Host, set_host};
+use soviet_sim::shape::Shape;
+use soviet_sim::key::{self, Key};
+use soviet_sim::controls;
+use soviet_sim::block;
+use soviet_sim::crafting;
+use soviet_sim::loot;
+use soviet_sim::defs::DefError;
+
+use std::env;
+use std::fs;
+use std::io::{self, Read, Write, BufWriter, Stdout};
+use std::path::PathBuf;
+use std::process::{self, Command, Stdio};
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const FRAME_TIME_MS: u64 = 1000 / 60;
+const RESIZE_CHECK_FRAMES: u64 = 30;
+
+// JavaScript keyCodes, as understood by key::parse_key
+const CODE_SHIFT: u8 = 16;
+const CODE_CTRL: u8 = 17;
+const CODE_ALT: u8 = 18;
+
+struct TerminalHost {
+    out: BufWriter<Stdout>,
+    rand_state: u64,
+    // Where stored values go, one file per key
+    store_dir: Option<PathBuf>,
+}
+
+impl TerminalHost {
+    fn new(store_dir: Option<PathBuf>) -> TerminalHost {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() ^ (d.subsec_nanos() as u64) << 32)
+            .unwrap_or(1);
+
+        TerminalHost {
+            out: BufWriter::new(io::stdout()),
+            rand_state: seed | 1,
+            store_dir: store_dir,
+        }
+    }
+}
+
+impl Host for TerminalHost {
+    fn put_char(&mut self, pos: (u16, u16), shape: &Shape) {
+        let _ = write!(
+            self.out,
+            "\x1b[{};{}H\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m{}",
+            pos.1 + 1, pos.0 + 1,
+            shape.col.0, shape.col.1, shape.col.2,
+            shape.bg.0, shape.bg.1, shape.bg.2,
+            shape.ch);
+    }
+
+    fn clear(&mut self) {
+        let _ = write!(self.out, "\x1b[0m\x1b[2J");
+    }
+
+    fn log(&mut self, _msg: &str) {
+        // Anything written here would end up on top of the game
+    }
+
+    fn rand(&mut self) -> f64 {
+        let mut x = self.rand_state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.rand_state = x;
+        (x.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn flush(&mut self) {
+        let _ = self.out.flush();
+    }
+
+    fn store(&mut self, key: &str, value: &str) {
+        match self.store_dir {
+            Some(ref dir) => {
+                let _ = fs::create_dir_all(dir).and_then(|_| fs::write(dir.join(key), value));
+            }
+            None => host::store_in_memory(key, value),
+        }
+    }
+
+    fn load(&mut self, key: &str) -> Option<String> {
+        match self.store_dir {
+            Some(ref dir) => fs::read_to_string(dir.join(key)).ok(),
+            None => host::load_from_memory(key),
+        }
+    }
+}
+
+/// Puts the terminal in raw mode, and back when dropped
+struct RawTerminal {
+    saved: String,
+}
+
+impl RawTerminal {
+    fn enter() -> RawTerminal {
+        let saved = stty(&["-g"]).unwrap_or_else(|| "sane".into());
+        stty(&["raw", "-echo"]);
+        print!("\x1b[?1049h\x1b[?25l");
+        let _ = io::stdout().flush();
+        RawTerminal { saved: saved.trim().into() }
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        print!("\x1b[0m\x1b[?25h\x1b[?1049l");
+        let _ = io::stdout().flush();
+        stty(&[&self.saved]);
+    }
+}
+
+fn stty(args: &[&str]) -> Option<String> {
+    Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .output()
+        .ok()
+        .and_then(|out| String::from_utf8(out.stdout).ok())
+}
+
+fn terminal_size() -> (u16, u16) {
+    stty(&["size"])
+        .and_then(|size| {
+            let mut parts = size.split_whitespace().map(|x| x.parse::<u16>().ok());
+            match (parts.next(), parts.next()) {
+                (Some(Some(rows)), Some(Some(cols))) => Some((cols, rows)),
+                _ => None,
+            }
+        })
+        .unwrap_or((80, 24))
+}
+
+fn spawn_input() -> Receiver<u8> {
+    let (send, recv) = channel();
+    thread::spawn(move || {
+        for byte in io::stdin().bytes() {
+            match byte {
+                Ok(b) => if send.send(b).is_err() { break; },
+                Err(_) => break,
+            }
+        }
+    });
+    recv
+}
+
+#[derive(Debug, Clone, Copy)]
+struct KeyPress {
+    code: u8,
+    shift: bool,
+    alt: bool,
+    ctrl: bool,
+}
+
+impl KeyPress {
+    fn plain(code: u8) -> KeyPress {
+        KeyPress { code: code, shift: false, alt: false, ctrl: false }
+    }
+}
+
+enum Input {
+    Press(KeyPress),
+    Quit,
+}
+
+/// Turn the bytes typed since the last frame into key presses
+fn parse_input(bytes: &[u8]) -> Vec<Input> {
+    let mut res = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            3 => res.push(Input::Quit), // Ctrl+C
+            0x1b if bytes.get(i + 1) == Some(&b'[') => {
+                // CSI sequence, like ESC [ A or ESC [ 1 ; 2 A
+                let start = i + 2;
+                let mut end = start;
+                while end < bytes.len() && !(bytes[end] as char).is_ascii_alphabetic() && bytes[end] != b'~' {
+                    end += 1;
+                }
+                if end < bytes.len() {
+                    let params = String::from_utf8_lossy(&bytes[start..end]).into_owned();
+                    let modifier = params.split(';').nth(1)
+                        .and_then(|m| m.parse::<u8>().ok())
+                        .map(|m| m.saturating_sub(1))
+                        .unwrap_or(0);
+                    let code = match bytes[end] {
+                        b'A' => Some(38),
+                        b'B' => Some(40),
+                        b'C' => Some(39),
+                        b'D' => Some(37),
+                        // F5 to F12, like ESC [ 1 5 ~
+                        b'~' => match params.split(';').next().and_then(|n| n.parse::<u8>().ok()) {
+                            Some(n @ 15) => Some(n + 101),
+                            Some(n @ 17...21) => Some(n + 100),
+                            Some(n @ 23...24) => Some(n + 99),
+                            _ => None,
+                        },
+                        _ => None,
+                    };
+                    if let Some(code) = code {
+                        res.push(Input::Press(KeyPress {
+                            code: code,
+                            shift: modifier & 1 != 0,
+                            alt: modifier & 2 != 0,
+                            ctrl: modifier & 4 != 0,
+                        }));
+                    }
+                }
+                i = end;
+            }
+            0x1b if bytes.get(i + 1) == Some(&b'O') && i + 2 < bytes.len() => {
+                // F1 to F4, ESC O P to ESC O S
+                if let b'P'...b'S' = bytes[i + 2] {
+                    res.push(Input::Press(KeyPress::plain(bytes[i + 2] - b'P' + 112)));
+                }
+                i += 2;
+            }
+            0x1b if i + 1 < bytes.len() => {
+                // Alt+key
+                if let Some(press) = parse_char(bytes[i + 1]) {
+                    res.push(Input::Press(KeyPress { alt: true, ..press }));
+                }
+                i += 1;
+            }
+            b => {
+                if let Some(press) = parse_char(b) {
+                    res.push(Input::Press(press));
+                }
+            }
+        }
+        i += 1;
+    }
+    res
+}
+
+fn parse_char(b: u8) -> Option<KeyPress> {
+    match b {
+        b'a'...b'z' => Some(KeyPress::plain(b - b'a' + b'A')),
+        b'A'...b'Z' => Some(KeyPress { shift: true, ..KeyPress::plain(b) }),
+        b'0'...b'9' => Some(KeyPress::plain(b)),
+        b'\r' | b'\n' => Some(KeyPress::plain(13)),
+        b'\t' => Some(KeyPress::plain(9)),
+        127 | 8 => Some(KeyPress::plain(8)),
+        b'+' => Some(KeyPress::plain(171)),
+        b'-' => Some(KeyPress::plain(173)),
+        b'=' => Some(KeyPress::plain(187)),
+        b' ' => Some(KeyPress::plain(32)),
+        0x1b => Some(KeyPress::plain(27)), // Escape on its own
+        b';' => Some(KeyPress::plain(186)),
+        b',' => Some(KeyPress::plain(188)),
+        b'.' => Some(KeyPress::plain(190)),
+        b'/' => Some(KeyPress::plain(191)),
+        b'`' => Some(KeyPress::plain(192)),
+        b'[' => Some(KeyPress::plain(219)),
+        b'\\' => Some(KeyPress::plain(220)),
+        b']' => Some(KeyPress::plain(221)),
+        b'\'' => Some(KeyPress::plain(222)),
+        _ => None,
+    }
+}
+
+/// Is this key held down as a modifier for some control, like M in M+arrow?
+fn is_modifier_letter(code: u8) -> bool {
+    match key::parse_key(code) {
+        Some(k @ Key::Letter(_)) => controls::is_modifier(&k),
+        _ => false,
+    }
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut bindings_path: Option<String> = None;
+    let mut store_dir: Option<PathBuf> = None;
+    while let Some(arg) = args.next() {
+        if arg == "--store" {
+            store_dir = args.next().map(PathBuf::from);
+            continue;
+        }
+        if arg == "--bindings" {
+            // It's fine if it doesn't exist yet, it's written when quitting
+            bindings_path = args.next();
+            if let Some(text) = bindings_path.as_ref().and_then(|path| fs::read_to_string(path).ok()) {
+                if let Err(e) = controls::load_bindings(&text) {
+                    eprintln!("Can't load key bindings: {}", e);
+                    process::exit(1);
+                }
+            }
+            continue;
+        }
+
+        let load: fn(&str) -> Result<usize, DefError> = match arg.as_str() {
+            "--blocks" => block::load_blocks,
+            "--recipes" => crafting::load_recipes,
+            "--loot" => loot::load_loot,
+            _ => {
+                eprintln!("Usage: terminal [--blocks FILE] [--recipes FILE] [--loot FILE] [--bindings FILE] [--store DIR]");
+                process::exit(1);
+            }
+        };
+        let path = args.next().unwrap_or_default();
+        let loaded = fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| load(&text).map_err(|e| e.to_string()));
+        if let Err(e) = loaded {
+            eprintln!("Can't load {}: {}", path, e);
+            process::exit(1);
+        }
+    }
+
+    set_host(Box::new(TerminalHost::new(store_dir)));
+    let _raw = RawTerminal::enter();
+
+    let mut size = terminal_size();
+    soviet_sim::start(size.0, size.1);
+
+    let input = spawn_input();
+    let mut held_letters: Vec<u8> = vec![];
+    let mut frame = 0u64;
+
+    'game: loop {
+        let frame_start = Instant::now();
+
+        let bytes: Vec<u8> = input.try_iter().collect();
+        for inp in parse_input(&bytes) {
+            let press = match inp {
+                Input::Quit => break 'game,
+                Input::Press(press) => press,
+            };
+
+            if is_modifier_letter(press.code) {
+                if !held_letters.contains(&press.code) {
+                    soviet_sim::key_down(press.code);
+                    held_letters.push(press.code);
+                }
+                continue;
+            }
+
+            let mods: Vec<u8> = [(press.shift, CODE_SHIFT), (press.ctrl, CODE_CTRL), (press.alt, CODE_ALT)]
+                .iter()
+                .filter(|&&(on, _)| on)
+                .map(|&(_, code)| code)
+                .collect();
+
+            for &m in &mods {
+                soviet_sim::key_down(m);
+            }
+            soviet_sim::key_down(press.code);
+            soviet_sim::key_up(press.code);
+            for &m in mods.iter().rev() {
+                soviet_sim::key_up(m);
+            }
+            for letter in held_letters.drain(..) {
+                soviet_sim::key_up(letter);
+            }
+        }
+
+        if frame % RESIZE_CHECK_FRAMES == 0 {
+            let new_size = terminal_size();
+            if new_size != size {
+                size = new_size;
+                soviet_sim::resize(size.0, size.1);
+            }
+        }
+
+        soviet_sim::tick();
+        frame += 1;
+
+        let elapsed = frame_start.elapsed();
+        let frame_time = Duration::from_millis(FRAME_TIME_MS);
+        if elapsed < frame_time {
+            thread::sleep(frame_time - elapsed);
+        }
+    }
+
+    if let Some(path) = bindings_path {
+        let _ = fs::write(&path, controls::bindings_to_string(&controls::bindings()));
+    }
+}
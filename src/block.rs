@@ -1,52 +1,53 @@
 use world::World;
 use shape::Shape;
-use ext::*;
 use entity::*;
+use move_dir::MoveDir;
+use defs::{self, DefError, Section};
 
 use std::sync::Mutex;
 
+const DEFAULT_BLOCKS: &str = include_str!("../defs/blocks.toml");
+
 lazy_static! {
-    pub static ref BLOCK_FUNCS: Mutex<Vec<fn(&mut World, u64)>>
-        = Mutex::new(vec![|_, _| {}]);
+    // Every known block, indexed by Block::index
+    static ref REGISTRY: Mutex<Vec<Block>> = Mutex::new(
+        parse_blocks(DEFAULT_BLOCKS)
+            .map(|blocks| blocks.into_iter().enumerate().map(|(i, block)| Block { index: i, ..block }).collect())
+            .unwrap_or_else(|e| panic!("Broken defs/blocks.toml: {}", e))
+        );
+}
+
+/// What happens to things that walk onto a block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WalkEffect {
+    Nothing,
+    Teleport,
+    Heal(u16),
+    Damage(u16),
+    Push(MoveDir),
 }
 
 #[derive(Debug, Clone)]
 pub struct Block {
+    pub id: String,
     pub name: String,
     pub desc: String,
     shape: Shape,
-    id: usize,
+    index: usize,
     passable: bool,
-    breakable: bool
+    breakable: bool,
+    walk: WalkEffect,
+    consume: bool,
 }
 
 impl PartialEq for Block {
     fn eq(&self, other: &Block) -> bool {
-        self.id == other.id
+        self.index == other.index
     }
 }
 impl Eq for Block {}
 
 impl Block {
-    fn new(shape: Shape, name: String, desc: String, passable: bool, breakable: bool, on_walk: fn(&mut World, u64))
-        -> Block
-    {
-        let mut blkf = BLOCK_FUNCS.lock().unwrap();
-        blkf.push(on_walk);
-
-        Block {
-            id: blkf.len() - 1,
-            name: name,
-            desc: desc,
-            passable: passable,
-            breakable: breakable,
-            shape: shape
-        }
-    }
-
-    #[inline]
-    pub fn get_id(&self) -> usize { self.id }
-
     #[inline]
     pub fn get_shape(&self) -> Shape { self.shape }
 
@@ -55,47 +56,126 @@ impl Block {
 
     #[inline]
     pub fn is_breakable(&self) -> bool { self.breakable }
+
+    #[inline]
+    pub fn get_walk(&self) -> WalkEffect { self.walk }
 }
 
-lazy_static! {
-    pub static ref GROUND: Block = Block::new(
-        Shape::new('.', (128, 128, 128), (0, 0, 0)),
-        "Ground".into(),
-        "Passive ground".into(),
-        true,
-        false,
-        |_, _| {}
-        );
+/// Find a block by its id
+pub fn by_id(id: &str) -> Option<Block> {
+    REGISTRY.lock().unwrap().iter()
+        .find(|block| block.id == id)
+        .cloned()
+}
 
-    pub static ref WALL: Block = Block::new(
-        Shape::new('#', (202, 195, 210), (0, 0, 0)),
-        "Wall".into(),
-        "An wall".into(),
-        false,
-        true,
-        |_, _| {}
-        );
+/// Find a block by its name. Old saves refer to blocks like this.
+pub fn by_name(name: &str) -> Option<Block> {
+    REGISTRY.lock().unwrap().iter()
+        .find(|block| block.name == name)
+        .cloned()
+}
 
-    pub static ref STONE: Block = Block::new(
-        Shape::new('&', (120, 140, 160), (10, 30, 50)),
-        "Stone".into(),
-        "A stone".into(),
-        false,
-        true,
-        |_, _| {}
-        );
+pub fn all() -> Vec<Block> {
+    REGISTRY.lock().unwrap().clone()
+}
+
+/// Add the blocks from a definition file, in the same format as defs/blocks.toml. A block with
+/// the id of an existing one replaces it, but blocks already in the world keep their old look.
+/// If anything in the file is wrong, nothing is added. Returns how many blocks were loaded.
+pub fn load_blocks(text: &str) -> Result<usize, DefError> {
+    let blocks = parse_blocks(text)?;
+    let count = blocks.len();
+
+    let mut registry = REGISTRY.lock().unwrap();
+    for mut block in blocks {
+        match registry.iter().position(|old| old.id == block.id) {
+            Some(idx) => {
+                block.index = idx;
+                registry[idx] = block;
+            }
+            None => {
+                block.index = registry.len();
+                registry.push(block);
+            }
+        }
+    }
+    Ok(count)
+}
+
+fn parse_blocks(text: &str) -> Result<Vec<Block>, DefError> {
+    defs::parse(text)?.iter().map(parse_block).collect()
+}
+
+fn parse_block(sec: &Section) -> Result<Block, DefError> {
+    sec.check_keys(&["name", "desc", "char", "fg", "bg", "passable", "breakable", "walk", "amount", "direction", "consume"])?;
+
+    if !sec.name.chars().all(|ch| ch.is_ascii_lowercase() || ch.is_ascii_digit() || ch == '_') {
+        return Err(DefError::new(sec.line, format!("Block id {} should only have a-z, 0-9 and _", sec.name)));
+    }
+
+    let ch = sec.require("char", sec.str("char"))?;
+    let mut chars = ch.chars();
+    let ch = match (chars.next(), chars.next()) {
+        (Some(ch), None) => ch,
+        _ => return Err(DefError::new(sec.line, format!("char in [{}] should be a single character", sec.name))),
+    };
+
+    let amount = sec.int("amount")?.unwrap_or(1);
+    if amount < 0 || amount > u16::max_value() as i64 {
+        return Err(DefError::new(sec.line, format!("amount in [{}] is out of range", sec.name)));
+    }
+    let amount = amount as u16;
+
+    let walk = match sec.str("walk")?.as_ref().map(|x| x.as_str()) {
+        None | Some("none") => WalkEffect::Nothing,
+        Some("teleport") => WalkEffect::Teleport,
+        Some("heal") => WalkEffect::Heal(amount),
+        Some("damage") => WalkEffect::Damage(amount),
+        Some("push") => {
+            let dir = match sec.require("direction", sec.str("direction"))?.as_str() {
+                "up" => MoveDir::Up,
+                "down" => MoveDir::Down,
+                "left" => MoveDir::Left,
+                "right" => MoveDir::Right,
+                dir => return Err(DefError::new(sec.line, format!("Unknown direction {} in [{}]", dir, sec.name))),
+            };
+            WalkEffect::Push(dir)
+        }
+        Some(walk) => return Err(DefError::new(sec.line, format!("Unknown walk effect {} in [{}]", walk, sec.name))),
+    };
+
+    Ok(Block {
+        id: sec.name.clone(),
+        name: sec.require("name", sec.str("name"))?,
+        desc: sec.str("desc")?.unwrap_or(String::new()),
+        shape: Shape::new(ch, sec.require("fg", sec.color("fg"))?, sec.color("bg")?.unwrap_or((0, 0, 0))),
+        index: 0,
+        passable: sec.bool("passable")?.unwrap_or(false),
+        breakable: sec.bool("breakable")?.unwrap_or(false),
+        walk: walk,
+        consume: sec.bool("consume")?.unwrap_or(false),
+    })
+}
 
-    pub static ref MOVER: Block = Block::new(
-        Shape::new('^', (255, 240, 30), (0, 0, 0)),
-        "Mover".into(),
-        "Moves anything that walks on it randomly to somewhere on the map".into(),
-        true,
-        true,
-        |world, id| {
-            let pos;
+/// Do whatever the block under an entity does to things that walk onto it
+pub fn walk_on(world: &mut World, en_id: u64) {
+    let pos = match world.entities.get(&en_id) {
+        Some(en) => en.get_pos(),
+        None => return,
+    };
+    let (walk, consume, name) =
+        match world.blocks.get(pos.0 as usize).and_then(|x| x.get(pos.1 as usize)) {
+            Some(block) => (block.walk, block.consume, block.name.clone()),
+            None => return,
+        };
+
+    let happened = match walk {
+        WalkEffect::Nothing => false,
+        WalkEffect::Teleport => {
+            let to;
             loop {
-                let x = (rand() * world.blocks.len() as f64) as usize;
-                let y = (rand() * world.blocks[0].len() as f64) as usize;
+                let x = (world.rng.rand() * world.blocks.len() as f64) as usize;
+                let y = (world.rng.rand() * world.blocks[0].len() as f64) as usize;
 
                 let passable = world.blocks.get(x as usize)
                     .and_then(|a| a.get(y as usize))
@@ -103,30 +183,56 @@ lazy_static! {
                     .unwrap_or(false);
 
                 if passable {
-                    pos = (x as u16, y as u16);
+                    to = (x as u16, y as u16);
                     break;
                 }
             }
-            if let Some(en) = world.entities.get_mut(&id) {
-                let epos = en.get_pos_mut();
-                epos.0 = pos.0;
-                epos.1 = pos.1;
+            world.set_entity_pos(en_id, to);
+            true
+        }
+        WalkEffect::Heal(amount) => {
+            if let Some(EntityWrapper::WPlayer(player)) = world.entities.get_mut(&en_id) {
+                player.hunger += amount;
+                true
+            } else {
+                false
             }
         }
-        );
-
-    pub static ref COMMUNISM: Block = Block::new(
-        Shape::new('☭', (253, 233, 54), (0, 0, 0)),
-        "COMMUNISM".into(),
-        "Heals you".into(),
-        true,
-        true,
-        |world, id| {
-            if let Some(EntityWrapper::WPlayer(player)) = world.entities.get_mut(&id) {
-                player.hunger += 1;
-                world.blocks[player.pos.0 as usize][player.pos.1 as usize] = GROUND.clone();
+        WalkEffect::Damage(amount) => {
+            if world.entities.contains_key(&en_id) {
+                world.hurt(en_id, amount, &name);
+                true
+            } else {
+                false
             }
         }
-        );
+        WalkEffect::Push(dir) => {
+            // Just moved there, so it doesn't set off whatever is at the new position
+            let to = dir.move_vec(pos);
+            let passable = world.blocks.get(to.0 as usize)
+                .and_then(|a| a.get(to.1 as usize))
+                .map(|a| a.is_passable())
+                .unwrap_or(false);
+
+            if passable && !world.is_blocked(to) {
+                world.set_entity_pos(en_id, to);
+                true
+            } else {
+                false
+            }
+        }
+    };
 
+    if happened && consume {
+        world.set_block(pos, GROUND.clone());
+    }
+}
+
+// The blocks the game itself needs. These ids have to exist in defs/blocks.toml.
+lazy_static! {
+    pub static ref GROUND: Block = by_id("ground").expect("No ground block");
+    pub static ref WALL: Block = by_id("wall").expect("No wall block");
+    pub static ref STONE: Block = by_id("stone").expect("No stone block");
+    pub static ref MOVER: Block = by_id("mover").expect("No mover block");
+    pub static ref COMMUNISM: Block = by_id("communism").expect("No communism block");
 }
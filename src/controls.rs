@@ -1,138 +1,327 @@
 use move_dir::MoveDir;
-use key::Key;
-
-use std::collections::{HashSet, HashMap};
-
-// From https://stackoverflow.com/a/27582993/1753929
-macro_rules! hashmap(
-    { $($key:expr => $value:expr),+, } => {
-        {
-            let mut m = ::std::collections::HashMap::new();
-            $(
-                m.insert($key, $value);
-            )+
-            m
-        }
-    };
-    { $x:tt } => { hashmap{ $x, } };
-);
+use key::{self, Key};
+use defs::DefError;
+
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter, Error};
+use std::sync::Mutex;
 
+/// A group of actions that are shown together in the menu
 #[derive(Debug, PartialEq, Eq)]
-pub struct Control<'a> {
-    pub modifiers: &'a [Key],
-    pub keys: HashMap<Key, Action>,
-    pub desc: &'a str,
+pub struct Control {
+    pub modifiers: Vec<Key>,
+    pub keys: Vec<(Key, Action)>,
+    pub desc: &'static str,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Action {
     Move(MoveDir),
     Run(MoveDir),
     Place(MoveDir),
     Break(MoveDir),
+    Drop(MoveDir),
+    Use(MoveDir),
     SuperMine(MoveDir),
-    IncActive, DecActive,
-    ToggleInventory, Die, Select
+    IncActive, DecActive, SelectSlot(usize),
+    ToggleInventory, Die, Undo, Select, CraftMax
+}
+
+impl Action {
+    /// The name bindings are saved under, like "break_up"
+    pub fn id(&self) -> String {
+        let with_dir = |name: &str, dir: &MoveDir| format!("{}_{}", name, format!("{:?}", dir).to_lowercase());
+        match self {
+            Action::Move(dir)      => with_dir("move", dir),
+            Action::Run(dir)       => with_dir("run", dir),
+            Action::Place(dir)     => with_dir("place", dir),
+            Action::Break(dir)     => with_dir("break", dir),
+            Action::Drop(dir)      => with_dir("drop", dir),
+            Action::Use(dir)       => with_dir("use", dir),
+            Action::SuperMine(dir) => with_dir("super_mine", dir),
+            Action::IncActive      => "next_slot".into(),
+            Action::DecActive      => "prev_slot".into(),
+            Action::SelectSlot(i)  => format!("slot_{}", i + 1),
+            Action::ToggleInventory => "inventory".into(),
+            Action::Die            => "die".into(),
+            Action::Undo           => "undo".into(),
+            Action::Select         => "select".into(),
+            Action::CraftMax       => "craft_max".into(),
+        }
+    }
+
+    pub fn by_id(id: &str) -> Option<Action> {
+        default_bindings().into_iter()
+            .map(|(action, _)| action)
+            .find(|action| action.id() == id)
+    }
+
+    /// What the action does, like "Break up"
+    pub fn desc(&self) -> String {
+        let mut desc = self.id().replace('_', " ");
+        if let Some(first) = desc.get_mut(0..1) {
+            first.make_ascii_uppercase();
+        }
+        desc
+    }
+}
+
+/// The keys that do an action: the key itself, pressed while all the modifiers are held
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Binding {
+    pub modifiers: Vec<Key>,
+    pub key: Key,
+}
+
+impl Binding {
+    pub fn new(modifiers: Vec<Key>, key: Key) -> Binding {
+        Binding {
+            modifiers: modifiers,
+            key: key,
+        }
+    }
+
+    fn same_keys(&self, other: &Binding) -> bool {
+        self.key == other.key
+            && self.modifiers.len() == other.modifiers.len()
+            && self.modifiers.iter().all(|m| other.modifiers.contains(m))
+    }
+
+    // Like "M+Up", read back by parse_bindings
+    fn to_names(&self) -> String {
+        self.modifiers.iter().chain(Some(&self.key))
+            .map(|k| k.name())
+            .collect::<Vec<_>>()
+            .join("+")
+    }
+}
+
+impl Display for Binding {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
+        for modifier in &self.modifiers {
+            write!(fmt, "{}+", modifier)?;
+        }
+        write!(fmt, "{}", self.key)
+    }
+}
+
+fn arrows(action: fn(MoveDir) -> Action) -> Vec<(Key, Action)> {
+    [MoveDir::Up, MoveDir::Right, MoveDir::Down, MoveDir::Left].iter()
+        .map(|&dir| (Key::Arrow(dir), action(dir)))
+        .collect()
 }
 
 lazy_static! {
-    pub static ref CONTROLS: Vec<Control<'static>> = vec![
+    /// The controls before anything is rebound
+    pub static ref DEFAULT_CONTROLS: Vec<Control> = vec![
         Control {
-            modifiers: &[Key::Shift, Key::Ctrl],
-            keys: hashmap!{
-                Key::Arrow(MoveDir::Up)    => Action::Run(MoveDir::Up),
-                Key::Arrow(MoveDir::Right) => Action::Run(MoveDir::Right),
-                Key::Arrow(MoveDir::Down)  => Action::Run(MoveDir::Down),
-                Key::Arrow(MoveDir::Left)  => Action::Run(MoveDir::Left),
-            },
+            modifiers: vec![Key::Shift, Key::Ctrl],
+            keys: arrows(Action::Run),
             desc: "Run",
         },
 
         Control {
-            modifiers: &[Key::Shift],
-            keys: hashmap!{
-                Key::Arrow(MoveDir::Right) => Action::IncActive,
-                Key::Arrow(MoveDir::Left)  => Action::DecActive,
-            },
+            modifiers: vec![Key::Shift],
+            keys: vec![
+                (Key::Arrow(MoveDir::Right), Action::IncActive),
+                (Key::Arrow(MoveDir::Left),  Action::DecActive),
+            ],
             desc: "Change the active slot",
         },
 
         Control {
-            modifiers: &[Key::Letter(12), Key::Alt],
-            keys: hashmap!{
-                Key::Arrow(MoveDir::Up)    => Action::SuperMine(MoveDir::Up),
-                Key::Arrow(MoveDir::Right) => Action::SuperMine(MoveDir::Right),
-                Key::Arrow(MoveDir::Down)  => Action::SuperMine(MoveDir::Down),
-                Key::Arrow(MoveDir::Left)  => Action::SuperMine(MoveDir::Left),
-            },
+            modifiers: vec![],
+            keys: (1..10).map(|d| (Key::Digit(d), Action::SelectSlot(d as usize - 1))).collect(),
+            desc: "Select a hotbar slot",
+        },
+
+        Control {
+            modifiers: vec![Key::Shift],
+            keys: vec![(Key::Enter, Action::CraftMax)],
+            desc: "Craft as many as possible",
+        },
+
+        Control {
+            modifiers: vec![Key::letter('M'), Key::Alt],
+            keys: arrows(Action::SuperMine),
             desc: "Supermine a block",
         },
 
         Control {
-            modifiers: &[Key::Letter(12)],
-            keys: hashmap!{
-                Key::Arrow(MoveDir::Up)    => Action::Break(MoveDir::Up),
-                Key::Arrow(MoveDir::Right) => Action::Break(MoveDir::Right),
-                Key::Arrow(MoveDir::Down)  => Action::Break(MoveDir::Down),
-                Key::Arrow(MoveDir::Left)  => Action::Break(MoveDir::Left),
-            },
+            modifiers: vec![Key::letter('M')],
+            keys: arrows(Action::Break),
             desc: "Break a block",
         },
 
         Control {
-            modifiers: &[Key::Letter(15)],
-            keys: hashmap!{
-                Key::Arrow(MoveDir::Up)    => Action::Place(MoveDir::Up),
-                Key::Arrow(MoveDir::Right) => Action::Place(MoveDir::Right),
-                Key::Arrow(MoveDir::Down)  => Action::Place(MoveDir::Down),
-                Key::Arrow(MoveDir::Left)  => Action::Place(MoveDir::Left),
-            },
+            modifiers: vec![Key::letter('P')],
+            keys: arrows(Action::Place),
             desc: "Place a block",
         },
 
         Control {
-            modifiers: &[],
-            keys: hashmap!{
-                Key::Arrow(MoveDir::Up)    => Action::Move(MoveDir::Up),
-                Key::Arrow(MoveDir::Right) => Action::Move(MoveDir::Right),
-                Key::Arrow(MoveDir::Down)  => Action::Move(MoveDir::Down),
-                Key::Arrow(MoveDir::Left)  => Action::Move(MoveDir::Left),
-            },
+            modifiers: vec![Key::letter('Q')],
+            keys: arrows(Action::Drop),
+            desc: "Drop the active stack",
+        },
+
+        Control {
+            modifiers: vec![Key::letter('E')],
+            keys: arrows(Action::Use),
+            desc: "Use the active item",
+        },
+
+        Control {
+            modifiers: vec![],
+            keys: arrows(Action::Move),
             desc: "Move the character",
         },
 
         Control {
-            modifiers: &[],
-            keys: hashmap!{ Key::Letter(8) => Action::ToggleInventory, }, // I
+            modifiers: vec![],
+            keys: vec![(Key::letter('I'), Action::ToggleInventory)],
             desc: "Open/close the inventory",
         },
 
         Control {
-            modifiers: &[],
-            keys: hashmap!{ Key::Letter(17) => Action::Die, }, // R
+            modifiers: vec![],
+            keys: vec![(Key::letter('R'), Action::Die)],
             desc: "Die",
         },
 
         Control {
-            modifiers: &[],
-            keys: hashmap!{ Key::Enter => Action::Select, },
+            modifiers: vec![],
+            keys: vec![(Key::letter('Z'), Action::Undo)],
+            desc: "Undo the last block placed or broken",
+        },
+
+        Control {
+            modifiers: vec![],
+            keys: vec![(Key::Enter, Action::Select)],
             desc: "Select",
         },
     ];
+
+    // What every action is bound to now, in the order of DEFAULT_CONTROLS
+    static ref BINDINGS: Mutex<Vec<(Action, Binding)>> = Mutex::new(default_bindings());
 }
 
-pub fn parse_control<'a>(key: &'a Key, pressed: &HashSet<Key>) -> Option<&'static Action> {
-    'outer: for control in CONTROLS.iter() {
-        for modifier in control.modifiers {
-            if !pressed.contains(modifier) {
-                continue 'outer;
-            }
+pub fn default_bindings() -> Vec<(Action, Binding)> {
+    DEFAULT_CONTROLS.iter()
+        .flat_map(|control| control.keys.iter().map(move |(key, action)| (*action, Binding::new(control.modifiers.clone(), key.clone()))))
+        .collect()
+}
+
+pub fn bindings() -> Vec<(Action, Binding)> {
+    BINDINGS.lock().unwrap().clone()
+}
+
+pub fn binding(action: Action) -> Option<Binding> {
+    BINDINGS.lock().unwrap().iter()
+        .find(|(other, _)| *other == action)
+        .map(|(_, binding)| binding.clone())
+}
+
+/// Bind an action to other keys. If that gets in the way of another action, nothing changes and
+/// that action is returned.
+pub fn rebind(action: Action, binding: Binding) -> Result<(), Action> {
+    let mut bindings = BINDINGS.lock().unwrap();
+    if let Some(other) = conflict(&bindings, action, &binding) {
+        return Err(other);
+    }
+    for (other, old) in bindings.iter_mut() {
+        if *other == action {
+            *old = binding.clone();
         }
-        for (k, action) in control.keys.iter() {
-            if k == key {
-                return Some(action);
+    }
+    Ok(())
+}
+
+pub fn reset_bindings() {
+    *BINDINGS.lock().unwrap() = default_bindings();
+}
+
+/// The action a binding would get in the way of, if any: one bound to the same keys, or one
+/// where the key of one is a modifier of the other, since then pressing the modifier would also
+/// do the action.
+pub fn conflict(bindings: &[(Action, Binding)], action: Action, binding: &Binding) -> Option<Action> {
+    bindings.iter()
+        .find(|(other_action, other)| *other_action != action && (
+            other.same_keys(binding)
+            || other.modifiers.contains(&binding.key)
+            || binding.modifiers.contains(&other.key)))
+        .map(|(other_action, _)| *other_action)
+}
+
+/// Is a key held as a modifier by some binding, like M in M+arrow?
+pub fn is_modifier(key: &Key) -> bool {
+    BINDINGS.lock().unwrap().iter().any(|(_, binding)| binding.modifiers.contains(key))
+}
+
+/// The bindings as text, one "action = keys" per line, for the host to store
+pub fn bindings_to_string(bindings: &[(Action, Binding)]) -> String {
+    bindings.iter()
+        .map(|(action, binding)| format!("{} = {}\n", action.id(), binding.to_names()))
+        .collect()
+}
+
+/// Read bindings written by bindings_to_string. Actions that aren't in the text keep the binding
+/// they have in `old`.
+pub fn parse_bindings(old: &[(Action, Binding)], text: &str) -> Result<Vec<(Action, Binding)>, DefError> {
+    let mut bindings = old.to_vec();
+    // The actions in the text and their lines
+    let mut seen: Vec<(Action, usize)> = vec![];
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let eq = line.find('=').ok_or_else(|| DefError::new(i + 1, "Expected action = keys".into()))?;
+        let id = line[..eq].trim();
+        let action = Action::by_id(id).ok_or_else(|| DefError::new(i + 1, format!("Unknown action {}", id)))?;
+        if seen.iter().any(|(other, _)| *other == action) {
+            return Err(DefError::new(i + 1, format!("{} is bound twice", id)));
+        }
+        seen.push((action, i + 1));
+
+        let mut keys = vec![];
+        for name in line[eq + 1..].split('+').map(|name| name.trim()) {
+            keys.push(key::parse_name(name).ok_or_else(|| DefError::new(i + 1, format!("Unknown key {:?}", name)))?);
+        }
+        let key = keys.pop().unwrap();
+        let binding = Binding::new(keys, key);
+        for (other, old) in bindings.iter_mut() {
+            if *other == action {
+                *old = binding.clone();
             }
         }
     }
-    None
+
+    for (action, line) in &seen {
+        let binding = &bindings.iter().find(|(other, _)| other == action).unwrap().1;
+        if let Some(other) = conflict(&bindings, *action, binding) {
+            return Err(DefError::new(*line, format!("{} and {} get in each other's way", action.id(), other.id())));
+        }
+    }
+    Ok(bindings)
+}
+
+/// Load bindings from text like bindings_to_string makes. If anything is wrong, nothing is
+/// changed. Returns how many actions are bound differently now.
+pub fn load_bindings(text: &str) -> Result<usize, DefError> {
+    let mut bindings = BINDINGS.lock().unwrap();
+    let new = parse_bindings(&bindings, text)?;
+    let count = new.iter().zip(bindings.iter()).filter(|(new, old)| new != old).count();
+    *bindings = new;
+    Ok(count)
+}
+
+/// The action a key does with the keys that are already held. The binding with the most
+/// modifiers wins, so M+arrow breaks instead of moving.
+pub fn parse_control(bindings: &[(Action, Binding)], key: &Key, pressed: &HashSet<Key>) -> Option<Action> {
+    bindings.iter()
+        .filter(|(_, binding)| binding.key == *key && binding.modifiers.iter().all(|m| pressed.contains(m)))
+        .max_by_key(|(_, binding)| binding.modifiers.len())
+        .map(|(action, _)| *action)
 }
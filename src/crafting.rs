@@ -1,50 +1,278 @@
-use block;
-use inventory::{InventoryItem, PICKAXE, SUPER_BOOTS};
+use inventory::{InventoryItem, Inventory};
+use block::{self, Block};
+use world::World;
+use entity::EntityWrapper;
+use move_dir::DIRECTIONS;
+use defs::{self, DefError, Section, Value};
+
+use std::sync::{Arc, Mutex};
+use std::fmt::{Display, Formatter, Error};
+
+// The most that can be crafted at once
+pub const MAX_CRAFTS: u64 = 99;
+
+const DEFAULT_RECIPES: &str = include_str!("../defs/recipes.toml");
+
+lazy_static! {
+    // In the order they are shown. Loading recipes swaps in a new list, so it can be shared
+    // without copying it.
+    static ref RECIPES: Mutex<Arc<Vec<Recipe>>> = Mutex::new(Arc::new(
+        parse_recipes(DEFAULT_RECIPES)
+            .and_then(|new| check_recipes(&[], new))
+            .unwrap_or_else(|e| panic!("Broken defs/recipes.toml: {}", e))
+        ));
+}
 
 #[derive(Clone)]
 pub struct Recipe {
-    pub out: InventoryItem,
+    pub id: String,
+    pub out: Vec<(InventoryItem, u64)>,
     pub needed: Vec<(InventoryItem, u64)>,
+    // Have to be in the inventory, but aren't used up
+    pub tools: Vec<InventoryItem>,
+    // Have to stand next to this block
+    pub station: Option<Block>,
 }
 
-lazy_static! {
-    pub static ref RECIPES: Vec<Recipe> = vec![
-        Recipe {
-            out: InventoryItem::Block(block::MOVER.clone()),
-            needed: vec![
-                (InventoryItem::Block(block::WALL.clone()), 20),
-                (InventoryItem::Bomb, 1),
-            ]
-        },
-        Recipe {
-            out: InventoryItem::Bomb,
-            needed: vec![
-                (InventoryItem::Bullet, 7),
-                (InventoryItem::Block(block::STONE.clone()), 10),
-            ]
-        },
-        Recipe {
-            out: InventoryItem::Bullet,
-            needed: vec![
-                (InventoryItem::Block(block::WALL.clone()), 5),
-            ]
-        },
-        Recipe {
-            out: SUPER_BOOTS,
-            needed: vec![
-                (InventoryItem::Block(block::MOVER.clone()), 2),
-                (InventoryItem::Bullet, 10),
-            ]
-        },
-        Recipe {
-            out: PICKAXE,
-            needed: vec![
-                (InventoryItem::Bomb, 1),
-                (InventoryItem::Block(block::MOVER.clone()), 2),
-                (InventoryItem::Block(block::WALL.clone()), 8),
-                (InventoryItem::Bullet, 7),
-                (InventoryItem::Block(block::STONE.clone()), 32),
-            ]
-        },
-    ];
+impl Recipe {
+    /// What the recipe makes, like "Bullet x3, Stone"
+    pub fn get_name(&self) -> String {
+        self.out.iter()
+            .map(|(item, amount)| if *amount == 1 { item.get_name() } else { format!("{} x{}", item.get_name(), amount) })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// What a player is missing to craft this where they are now
+    pub fn missing(&self, world: &World, en_id: u64) -> Vec<Missing> {
+        let player = match world.entities.get(&en_id) {
+            Some(EntityWrapper::WPlayer(player)) => player,
+            _ => return vec![],
+        };
+
+        let mut missing = vec![];
+        for (needed, amount) in self.needed.iter() {
+            let have = player.inventory.count(needed);
+            if have < *amount {
+                missing.push(Missing::Item(needed.clone(), amount - have));
+            }
+        }
+        for tool in self.tools.iter() {
+            if !has_tool(&player.inventory, tool) {
+                missing.push(Missing::Tool(tool.clone()));
+            }
+        }
+        match self.station {
+            Some(ref station) if !self.at_station(world, player.pos) => missing.push(Missing::Station(station.clone())),
+            _ => {}
+        }
+        missing
+    }
+
+    pub fn can_craft(&self, world: &World, en_id: u64) -> bool {
+        self.missing(world, en_id).is_empty()
+    }
+
+    /// How many times this could be crafted with the items in an inventory, at most MAX_CRAFTS
+    pub fn max_crafts(&self, inventory: &Inventory) -> u64 {
+        self.needed.iter()
+            .map(|(needed, amount)| inventory.count(needed) / amount)
+            .min()
+            .unwrap_or(MAX_CRAFTS)
+            .min(MAX_CRAFTS)
+    }
+
+    pub fn has_tools(&self, inventory: &Inventory) -> bool {
+        self.tools.iter().all(|tool| has_tool(inventory, tool))
+    }
+
+    pub fn at_station(&self, world: &World, pos: (u16, u16)) -> bool {
+        match self.station {
+            Some(ref station) => DIRECTIONS.iter().any(|dir| {
+                let (x, y) = dir.move_vec(pos);
+                world.blocks.get(x as usize).and_then(|col| col.get(y as usize)) == Some(station)
+            }),
+            None => true,
+        }
+    }
+}
+
+/// Something that stops a recipe from being crafted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Missing {
+    // How many more are needed
+    Item(InventoryItem, u64),
+    Tool(InventoryItem),
+    Station(Block),
+}
+
+impl Display for Missing {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
+        match self {
+            Missing::Item(item, more) => write!(fmt, "{} more {}", more, item.get_name()),
+            Missing::Tool(tool) => write!(fmt, "a {}", tool.get_id().replace('_', " ")),
+            Missing::Station(block) => write!(fmt, "a {} next to you", block.name),
+        }
+    }
+}
+
+fn has_tool(inventory: &Inventory, tool: &InventoryItem) -> bool {
+    inventory.slots().iter().any(|(item, count)|
+        *count > 0 && item.get_id() == tool.get_id() && item.durability().map(|(left, _)| left > 0).unwrap_or(true))
+}
+
+pub fn recipes() -> Arc<Vec<Recipe>> {
+    RECIPES.lock().unwrap().clone()
+}
+
+/// Add the recipes from a definition file, in the same format as defs/recipes.toml. A recipe
+/// with the id of an existing one replaces it. If anything in the file is wrong, nothing is
+/// changed. Returns how many recipes were loaded.
+pub fn load_recipes(text: &str) -> Result<usize, DefError> {
+    let new = parse_recipes(text)?;
+    let count = new.len();
+
+    let mut recipes = RECIPES.lock().unwrap();
+    *recipes = Arc::new(check_recipes(&recipes, new)?);
+    Ok(count)
+}
+
+// A recipe and the line its output is on, for errors
+fn parse_recipes(text: &str) -> Result<Vec<(Recipe, usize)>, DefError> {
+    defs::parse(text)?.iter().map(parse_recipe).collect()
+}
+
+fn parse_recipe(sec: &Section) -> Result<(Recipe, usize), DefError> {
+    sec.check_keys(&["out", "needs", "tools", "station"])?;
+
+    let out = match sec.str("out") {
+        Ok(Some(id)) => vec![(item_by_id(&id, sec.key_line("out"))?, 1)],
+        _ => item_list(sec, "out")?,
+    };
+    // Required, or the recipe would make things out of nothing
+    let needed = item_list(sec, "needs")?;
+
+    let mut tools: Vec<InventoryItem> = vec![];
+    for val in sec.list("tools")?.unwrap_or(vec![]) {
+        let line = sec.key_line("tools");
+        let tool = match val {
+            Value::Str(ref id) => item_by_id(id, line)?,
+            _ => return Err(DefError::new(line, format!("tools in [{}] should be a list of items", sec.name))),
+        };
+        if tools.iter().chain(needed.iter().map(|(item, _)| item)).any(|other| other.get_id() == tool.get_id()) {
+            return Err(DefError::new(line, format!("[{}] needs {} twice", sec.name, tool.get_id())));
+        }
+        tools.push(tool);
+    }
+
+    let station = match sec.str("station")? {
+        Some(id) => Some(block::by_id(&id).ok_or_else(|| DefError::new(sec.key_line("station"), format!("Unknown block {}", id)))?),
+        None => None,
+    };
+
+    for (made, _) in &out {
+        if needed.iter().map(|(item, _)| item).chain(tools.iter()).any(|item| item.get_id() == made.get_id()) {
+            return Err(DefError::new(sec.line, format!("[{}] needs {}, which is what it makes", sec.name, made.get_id())));
+        }
+    }
+
+    let recipe = Recipe {
+        id: sec.name.clone(),
+        out: out,
+        needed: needed,
+        tools: tools,
+        station: station,
+    };
+    Ok((recipe, sec.key_line("out")))
+}
+
+// A list of [item, amount]
+fn item_list(sec: &Section, key: &str) -> Result<Vec<(InventoryItem, u64)>, DefError> {
+    let line = sec.key_line(key);
+    let mut items: Vec<(InventoryItem, u64)> = vec![];
+    for val in sec.require(key, sec.list(key))? {
+        let (item, amount) = match val {
+            Value::List(ref pair) if pair.len() == 2 => match (&pair[0], &pair[1]) {
+                (Value::Str(id), Value::Int(amount)) => (item_by_id(id, line)?, *amount),
+                _ => return Err(DefError::new(line, format!("{} in [{}] should be a list of [item, amount]", key, sec.name))),
+            },
+            _ => return Err(DefError::new(line, format!("{} in [{}] should be a list of [item, amount]", key, sec.name))),
+        };
+
+        if amount <= 0 {
+            return Err(DefError::new(line, format!("{} in [{}] has {} {}, it should be at least 1", key, sec.name, amount, item.get_id())));
+        }
+        if items.iter().any(|(other, _)| other.get_id() == item.get_id()) {
+            return Err(DefError::new(line, format!("{} is in {} of [{}] twice", item.get_id(), key, sec.name)));
+        }
+        items.push((item, amount as u64));
+    }
+    if items.is_empty() {
+        return Err(DefError::new(line, format!("{} in [{}] is empty", key, sec.name)));
+    }
+    Ok(items)
+}
+
+fn item_by_id(id: &str, line: usize) -> Result<InventoryItem, DefError> {
+    InventoryItem::by_id(id).ok_or_else(|| DefError::new(line, format!("Unknown item {}", id)))
+}
+
+// Put new recipes in with the old ones and make sure no two make the same thing, and that no
+// recipe needs something that can only be made from what it makes
+fn check_recipes(old: &[Recipe], new: Vec<(Recipe, usize)>) -> Result<Vec<Recipe>, DefError> {
+    let mut recipes = old.to_vec();
+    for (recipe, _) in &new {
+        match recipes.iter().position(|old| old.id == recipe.id) {
+            Some(idx) => recipes[idx] = recipe.clone(),
+            None => recipes.push(recipe.clone()),
+        }
+    }
+
+    for (recipe, line) in &new {
+        for (made, _) in &recipe.out {
+            let other = recipes.iter()
+                .find(|other| other.id != recipe.id && other.out.iter().any(|(item, _)| item.get_id() == made.get_id()));
+            if let Some(other) = other {
+                return Err(DefError::new(*line, format!("[{}] makes {}, but so does [{}]", recipe.id, made.get_id(), other.id)));
+            }
+        }
+    }
+
+    // The old recipes had no circles, so any circle goes through a new one
+    for (recipe, line) in &new {
+        let start = recipes.iter().position(|other| other.id == recipe.id).unwrap_or(0);
+        let mut path = vec![start];
+        if find_circle(&recipes, &mut path, &mut vec![false; recipes.len()]) {
+            let ids = path.iter().map(|&idx| format!("[{}]", recipes[idx].id)).collect::<Vec<_>>();
+            return Err(DefError::new(*line, format!("{} needs {}, which goes in a circle", ids[0], ids[1..].join(" needs "))));
+        }
+    }
+    Ok(recipes)
+}
+
+// Follow what the last recipe in path needs to the recipes that make it, until getting back to
+// the first one
+fn find_circle(recipes: &[Recipe], path: &mut Vec<usize>, seen: &mut Vec<bool>) -> bool {
+    let current = path[path.len() - 1];
+    for (needed, _) in &recipes[current].needed {
+        for (idx, maker) in recipes.iter().enumerate() {
+            if !maker.out.iter().any(|(item, _)| item.get_id() == needed.get_id()) {
+                continue;
+            }
+            if idx == path[0] {
+                path.push(idx);
+                return true;
+            }
+            if seen[idx] {
+                continue;
+            }
+            seen[idx] = true;
+            path.push(idx);
+            if find_circle(recipes, path, seen) {
+                return true;
+            }
+            path.pop();
+        }
+    }
+    false
 }
@@ -0,0 +1,277 @@
+// A small parser for the definition files in defs/, a subset of TOML:
+//
+//     # comment
+//     [section]
+//     key = "string"
+//     number = 12
+//     flag = true
+//     list = [1, 2, 3]
+//     long_list = [
+//         [1, 2],
+//         [3, 4],
+//     ]
+
+use std::fmt::{Display, Formatter, Error};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DefError {
+    pub line: usize,
+    pub msg: String,
+}
+
+impl DefError {
+    pub fn new(line: usize, msg: String) -> DefError {
+        DefError { line: line, msg: msg }
+    }
+}
+
+impl Display for DefError {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
+        write!(fmt, "Line {}: {}", self.line, self.msg)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Str(String),
+    Int(i64),
+    Bool(bool),
+    List(Vec<Value>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Section {
+    pub name: String,
+    pub line: usize,
+    pub entries: Vec<(String, Value, usize)>,
+}
+
+impl Section {
+    fn get(&self, key: &str) -> Option<&(String, Value, usize)> {
+        self.entries.iter().find(|entry| entry.0 == key)
+    }
+
+    fn error(&self, key: &str, expected: &str) -> DefError {
+        DefError::new(self.key_line(key), format!("{} in [{}] should be {}", key, self.name, expected))
+    }
+
+    /// Complain about keys that aren't known, they are probably typos
+    pub fn check_keys(&self, known: &[&str]) -> Result<(), DefError> {
+        for (key, _, line) in &self.entries {
+            if !known.contains(&key.as_str()) {
+                return Err(DefError::new(*line, format!("Unknown key {} in [{}]", key, self.name)));
+            }
+        }
+        Ok(())
+    }
+
+    /// The line a key is on, or the header's if it isn't there
+    pub fn key_line(&self, key: &str) -> usize {
+        self.get(key).map(|entry| entry.2).unwrap_or(self.line)
+    }
+
+    pub fn has(&self, key: &str) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn str(&self, key: &str) -> Result<Option<String>, DefError> {
+        match self.get(key) {
+            Some((_, Value::Str(s), _)) => Ok(Some(s.clone())),
+            Some(_) => Err(self.error(key, "a string")),
+            None => Ok(None),
+        }
+    }
+
+    pub fn int(&self, key: &str) -> Result<Option<i64>, DefError> {
+        match self.get(key) {
+            Some((_, Value::Int(i), _)) => Ok(Some(*i)),
+            Some(_) => Err(self.error(key, "a number")),
+            None => Ok(None),
+        }
+    }
+
+    pub fn bool(&self, key: &str) -> Result<Option<bool>, DefError> {
+        match self.get(key) {
+            Some((_, Value::Bool(b), _)) => Ok(Some(*b)),
+            Some(_) => Err(self.error(key, "true or false")),
+            None => Ok(None),
+        }
+    }
+
+    pub fn list(&self, key: &str) -> Result<Option<Vec<Value>>, DefError> {
+        match self.get(key) {
+            Some((_, Value::List(l), _)) => Ok(Some(l.clone())),
+            Some(_) => Err(self.error(key, "a list")),
+            None => Ok(None),
+        }
+    }
+
+    /// A colour written as [r, g, b]
+    pub fn color(&self, key: &str) -> Result<Option<(u8, u8, u8)>, DefError> {
+        let list = match self.list(key)? {
+            Some(list) => list,
+            None => return Ok(None),
+        };
+        let mut rgb = vec![];
+        for val in list {
+            match val {
+                Value::Int(i) if i >= 0 && i < 256 => rgb.push(i as u8),
+                _ => return Err(self.error(key, "a list of three numbers 0-255")),
+            }
+        }
+        if rgb.len() != 3 {
+            return Err(self.error(key, "a list of three numbers 0-255"));
+        }
+        Ok(Some((rgb[0], rgb[1], rgb[2])))
+    }
+
+    /// Like the getters above, but the key has to be there
+    pub fn require<T>(&self, key: &str, val: Result<Option<T>, DefError>) -> Result<T, DefError> {
+        match val? {
+            Some(val) => Ok(val),
+            None => Err(DefError::new(self.line, format!("[{}] is missing {}", self.name, key))),
+        }
+    }
+}
+
+pub fn parse(text: &str) -> Result<Vec<Section>, DefError> {
+    let mut sections: Vec<Section> = vec![];
+
+    let mut lines = text.lines().enumerate();
+    while let Some((i, line)) = lines.next() {
+        let line_nr = i + 1;
+        let mut line = strip_comment(line).trim().to_string();
+        if line.is_empty() {
+            continue;
+        }
+
+        if line.starts_with('[') {
+            if !line.ends_with(']') {
+                return Err(DefError::new(line_nr, "Section header is missing a ]".into()));
+            }
+            let name = line[1..line.len() - 1].trim();
+            if name.is_empty() {
+                return Err(DefError::new(line_nr, "Empty section name".into()));
+            }
+            if sections.iter().any(|sec| sec.name == name) {
+                return Err(DefError::new(line_nr, format!("[{}] is defined twice", name)));
+            }
+            sections.push(Section { name: name.into(), line: line_nr, entries: vec![] });
+            continue;
+        }
+
+        let eq = match line.find('=') {
+            Some(eq) => eq,
+            None => return Err(DefError::new(line_nr, format!("Expected key = value, got {:?}", line))),
+        };
+        // Lists can go on over several lines
+        while unclosed_brackets(&line[eq..]) > 0 {
+            match lines.next() {
+                Some((_, next)) => {
+                    line.push(' ');
+                    line.push_str(strip_comment(next).trim());
+                }
+                None => break,
+            }
+        }
+
+        let key = line[..eq].trim();
+        let (value, rest) = parse_value(line[eq + 1..].trim(), line_nr)?;
+        if !rest.trim().is_empty() {
+            return Err(DefError::new(line_nr, format!("Unexpected {:?} after value", rest.trim())));
+        }
+
+        match sections.last_mut() {
+            Some(section) => {
+                if section.get(key).is_some() {
+                    return Err(DefError::new(line_nr, format!("{} is set twice in [{}]", key, section.name)));
+                }
+                section.entries.push((key.into(), value, line_nr));
+            }
+            None => return Err(DefError::new(line_nr, format!("{} is outside of any section", key))),
+        }
+    }
+
+    Ok(sections)
+}
+
+// How many more [ than ] there are outside of strings
+fn unclosed_brackets(text: &str) -> i32 {
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    for ch in text.chars() {
+        match ch {
+            '\\' if in_string => { escaped = !escaped; continue; }
+            '"' if !escaped => in_string = !in_string,
+            '[' if !in_string => depth += 1,
+            ']' if !in_string => depth -= 1,
+            _ => {}
+        }
+        escaped = false;
+    }
+    depth
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, ch) in line.char_indices() {
+        match ch {
+            '\\' if in_string => { escaped = !escaped; continue; }
+            '"' if !escaped => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => {}
+        }
+        escaped = false;
+    }
+    line
+}
+
+// Parse a value from the start of text, returning it and what's left
+fn parse_value(text: &str, line: usize) -> Result<(Value, &str), DefError> {
+    if text.starts_with('"') {
+        let mut res = String::new();
+        let mut chars = text.char_indices().skip(1);
+        while let Some((i, ch)) = chars.next() {
+            match ch {
+                '"' => return Ok((Value::Str(res), &text[i + 1..])),
+                '\\' => match chars.next() {
+                    Some((_, 'n')) => res.push('\n'),
+                    Some((_, ch)) => res.push(ch),
+                    None => break,
+                },
+                ch => res.push(ch),
+            }
+        }
+        Err(DefError::new(line, "String is missing its closing \"".into()))
+    } else if text.starts_with('[') {
+        let mut items = vec![];
+        let mut rest = text[1..].trim_start();
+        loop {
+            if rest.starts_with(']') {
+                return Ok((Value::List(items), &rest[1..]));
+            }
+            let (item, after) = parse_value(rest, line)?;
+            items.push(item);
+            rest = after.trim_start();
+            if rest.starts_with(',') {
+                rest = rest[1..].trim_start();
+            } else if !rest.starts_with(']') {
+                return Err(DefError::new(line, "Expected , or ] in list".into()));
+            }
+        }
+    } else {
+        let end = text.find(|ch: char| ch == ',' || ch == ']' || ch.is_whitespace()).unwrap_or(text.len());
+        let word = &text[..end];
+        let value = match word {
+            "true" => Value::Bool(true),
+            "false" => Value::Bool(false),
+            _ => match word.parse() {
+                Ok(i) => Value::Int(i),
+                Err(_) => return Err(DefError::new(line, format!("Can't understand value {:?}", word))),
+            },
+        };
+        Ok((value, &text[end..]))
+    }
+}
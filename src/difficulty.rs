@@ -1,114 +1,259 @@
+// How hard a run is: one of the five historical presets, or custom values for every parameter.
+//
+// Custom difficulties are shared as a code with the values of every parameter, in the order of
+// PARAMS, separated by dashes, like "5-60-1000-20-7-5-5".
+
+use std::fmt::{Display, Formatter, Error};
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[allow(unused)]
-pub enum Difficulty {
-    Easy = 0, Medium = 1, Hard = 2, Extreme = 3, Reality = 4
+pub enum Preset {
+    Easy = 0, Medium = 1, Hard = 2, Extreme = 3, Reality = 4, Custom = 5
 }
 
-impl Difficulty {
-    pub fn to_string(self) -> String {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Difficulty {
+    pub preset: Preset,
+    pub josef_health: u16,
+    // Ticks between Josef's steps
+    pub josef_speed: u16,
+    // Ticks between Josef sending out police
+    pub josef_police_rate: u16,
+    // Ticks between the steps of police
+    pub police_speed: u16,
+    // Ticks between police hitting the player
+    pub police_hurt_rate: u16,
+    // The most communism one police drops, and one over the chance of them stopping at each block
+    pub communism_drop_rate: u16,
+    pub start_health: u16,
+}
+
+/// Everything that can be changed in a custom difficulty
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Param {
+    JosefHealth,
+    JosefSpeed,
+    JosefPoliceRate,
+    PoliceSpeed,
+    PoliceHurtRate,
+    CommunismDropRate,
+    StartHealth,
+}
+
+pub const PARAMS: &[Param] = &[
+    Param::JosefHealth,
+    Param::JosefSpeed,
+    Param::JosefPoliceRate,
+    Param::PoliceSpeed,
+    Param::PoliceHurtRate,
+    Param::CommunismDropRate,
+    Param::StartHealth,
+];
+
+impl Param {
+    pub fn desc(self) -> &'static str {
         match self {
-            Difficulty::Easy    => "2014 (Easy)".to_string(),
-            Difficulty::Medium  => "1989 (Medium)".to_string(),
-            Difficulty::Hard    => "1917 (Hard)".to_string(),
-            Difficulty::Extreme => "1922 (Extreme)".to_string(),
-            Difficulty::Reality => "1944 (Reality)".to_string(),
+            Param::JosefHealth       => "Josef's health",
+            Param::JosefSpeed        => "Ticks between Josef's steps",
+            Param::JosefPoliceRate   => "Ticks between police from Josef",
+            Param::PoliceSpeed       => "Ticks between police steps",
+            Param::PoliceHurtRate    => "Ticks between police hits",
+            Param::CommunismDropRate => "Communism dropped by police",
+            Param::StartHealth       => "Your starting health",
         }
     }
 
-    pub fn harder(self) -> Difficulty {
+    /// The smallest and largest values allowed
+    pub fn range(self) -> (u16, u16) {
         match self {
-            Difficulty::Easy    => Difficulty::Medium,
-            Difficulty::Medium  => Difficulty::Hard,
-            Difficulty::Hard    => Difficulty::Extreme,
-            Difficulty::Extreme => Difficulty::Reality,
-            Difficulty::Reality => Difficulty::Reality,
+            Param::JosefHealth       => (1, 999),
+            Param::JosefSpeed        => (1, 600),
+            Param::JosefPoliceRate   => (10, 9990),
+            Param::PoliceSpeed       => (1, 600),
+            Param::PoliceHurtRate    => (1, 600),
+            Param::CommunismDropRate => (1, 99),
+            Param::StartHealth       => (1, 99),
         }
     }
 
-    pub fn easier(self) -> Difficulty {
+    /// How much the editor changes it by
+    pub fn step(self) -> u16 {
         match self {
-            Difficulty::Reality => Difficulty::Extreme,
-            Difficulty::Extreme => Difficulty::Hard,
-            Difficulty::Hard    => Difficulty::Medium,
-            Difficulty::Medium  => Difficulty::Easy,
-            Difficulty::Easy    => Difficulty::Easy,
+            Param::JosefSpeed      => 5,
+            Param::JosefPoliceRate => 50,
+            _ => 1,
         }
     }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CodeError {
+    WrongLength(usize),
+    NotANumber(String),
+    OutOfRange(Param, u16),
+}
 
-    #[inline(always)]
-    pub fn get_josef_police_rate(self) -> u16 {
+impl Display for CodeError {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
         match self {
-            Difficulty::Easy => 1000,
-            Difficulty::Medium => 700,
-            Difficulty::Hard => 400,
-            Difficulty::Extreme => 200,
-            Difficulty::Reality => 50
+            CodeError::WrongLength(len) => write!(fmt, "A code has {} numbers, not {}", PARAMS.len(), len),
+            CodeError::NotANumber(part) => write!(fmt, "{:?} isn't a number", part),
+            CodeError::OutOfRange(param, value) => {
+                let (min, max) = param.range();
+                write!(fmt, "{} can't be {}, only {} to {}", param.desc(), value, min, max)
+            }
         }
     }
+}
 
-    #[inline(always)]
-    pub fn get_josef_speed(self) -> u16 {
-        match self {
-            Difficulty::Easy => 60,
-            Difficulty::Medium => 40,
-            Difficulty::Hard => 30,
-            Difficulty::Extreme => 20,
-            Difficulty::Reality => 15
+impl Difficulty {
+    pub const EASY: Difficulty = Difficulty {
+        preset: Preset::Easy,
+        josef_health: 3,
+        josef_speed: 60,
+        josef_police_rate: 1000,
+        police_speed: 20,
+        police_hurt_rate: 7,
+        communism_drop_rate: 5,
+        start_health: 5,
+    };
+
+    pub const MEDIUM: Difficulty = Difficulty {
+        preset: Preset::Medium,
+        josef_health: 5,
+        josef_speed: 40,
+        josef_police_rate: 700,
+        police_speed: 15,
+        police_hurt_rate: 5,
+        communism_drop_rate: 4,
+        start_health: 4,
+    };
+
+    pub const HARD: Difficulty = Difficulty {
+        preset: Preset::Hard,
+        josef_health: 7,
+        josef_speed: 30,
+        josef_police_rate: 400,
+        police_speed: 12,
+        police_hurt_rate: 4,
+        communism_drop_rate: 3,
+        start_health: 3,
+    };
+
+    pub const EXTREME: Difficulty = Difficulty {
+        preset: Preset::Extreme,
+        josef_health: 13,
+        josef_speed: 20,
+        josef_police_rate: 200,
+        police_speed: 10,
+        police_hurt_rate: 3,
+        communism_drop_rate: 2,
+        start_health: 2,
+    };
+
+    pub const REALITY: Difficulty = Difficulty {
+        preset: Preset::Reality,
+        josef_health: 17,
+        josef_speed: 15,
+        josef_police_rate: 50,
+        police_speed: 7,
+        police_hurt_rate: 1,
+        communism_drop_rate: 1,
+        start_health: 1,
+    };
+
+    pub fn to_string(self) -> String {
+        match self.preset {
+            Preset::Easy    => "2014 (Easy)".to_string(),
+            Preset::Medium  => "1989 (Medium)".to_string(),
+            Preset::Hard    => "1917 (Hard)".to_string(),
+            Preset::Extreme => "1922 (Extreme)".to_string(),
+            Preset::Reality => "1944 (Reality)".to_string(),
+            Preset::Custom  => format!("Custom ({})", self.code()),
         }
     }
 
-    #[inline(always)]
-    pub fn get_police_speed(self) -> u16 {
-        match self {
-            Difficulty::Easy => 20,
-            Difficulty::Medium => 15,
-            Difficulty::Hard => 12,
-            Difficulty::Extreme => 10,
-            Difficulty::Reality => 7
+    /// The next preset up. Custom difficulties and Reality stay as they are.
+    pub fn harder(self) -> Difficulty {
+        match self.preset {
+            Preset::Custom => self,
+            preset => Difficulty::from_index(preset as u8 + 1).unwrap_or(self),
         }
     }
 
-    #[inline(always)]
-    pub fn get_police_hurt_rate(self) -> u16 {
-        match self {
-            Difficulty::Easy => 7,
-            Difficulty::Medium => 5,
-            Difficulty::Hard => 4,
-            Difficulty::Extreme => 3,
-            Difficulty::Reality => 1
+    /// The next preset down. Custom difficulties and Easy stay as they are.
+    pub fn easier(self) -> Difficulty {
+        match self.preset {
+            Preset::Custom | Preset::Easy => self,
+            preset => Difficulty::from_index(preset as u8 - 1).unwrap_or(self),
         }
     }
 
-    #[inline(always)]
-    pub fn get_josef_health(self) -> u16 {
-        match self {
-            Difficulty::Easy => 3,
-            Difficulty::Medium => 5,
-            Difficulty::Hard => 7,
-            Difficulty::Extreme => 13,
-            Difficulty::Reality => 17
+    /// One of the presets
+    pub fn from_index(idx: u8) -> Option<Difficulty> {
+        match idx {
+            0 => Some(Difficulty::EASY),
+            1 => Some(Difficulty::MEDIUM),
+            2 => Some(Difficulty::HARD),
+            3 => Some(Difficulty::EXTREME),
+            4 => Some(Difficulty::REALITY),
+            _ => None,
         }
     }
 
-    #[inline(always)]
-    pub fn get_communism_drop_rate(self) -> f64 {
-        match self {
-            Difficulty::Easy => 5.,
-            Difficulty::Medium => 4.,
-            Difficulty::Hard => 3.,
-            Difficulty::Extreme => 2.,
-            Difficulty::Reality => 1.,
+    /// The same values, as a custom difficulty that can be changed
+    pub fn custom(self) -> Difficulty {
+        Difficulty { preset: Preset::Custom, ..self }
+    }
+
+    pub fn get(&self, param: Param) -> u16 {
+        match param {
+            Param::JosefHealth       => self.josef_health,
+            Param::JosefSpeed        => self.josef_speed,
+            Param::JosefPoliceRate   => self.josef_police_rate,
+            Param::PoliceSpeed       => self.police_speed,
+            Param::PoliceHurtRate    => self.police_hurt_rate,
+            Param::CommunismDropRate => self.communism_drop_rate,
+            Param::StartHealth       => self.start_health,
         }
     }
 
-    #[inline(always)]
-    pub fn get_start_health(self) -> u16 {
-        match self {
-            Difficulty::Easy => 5,
-            Difficulty::Medium => 4,
-            Difficulty::Hard => 3,
-            Difficulty::Extreme => 2,
-            Difficulty::Reality => 1,
+    /// Change a value, kept within the range of the parameter. This makes the difficulty custom.
+    pub fn set(&mut self, param: Param, value: u16) {
+        let (min, max) = param.range();
+        let value = value.max(min).min(max);
+        self.preset = Preset::Custom;
+        match param {
+            Param::JosefHealth       => self.josef_health = value,
+            Param::JosefSpeed        => self.josef_speed = value,
+            Param::JosefPoliceRate   => self.josef_police_rate = value,
+            Param::PoliceSpeed       => self.police_speed = value,
+            Param::PoliceHurtRate    => self.police_hurt_rate = value,
+            Param::CommunismDropRate => self.communism_drop_rate = value,
+            Param::StartHealth       => self.start_health = value,
+        }
+    }
+
+    /// The code to share this difficulty with
+    pub fn code(&self) -> String {
+        PARAMS.iter().map(|&param| self.get(param).to_string()).collect::<Vec<_>>().join("-")
+    }
+
+    /// A custom difficulty from a code
+    pub fn from_code(code: &str) -> Result<Difficulty, CodeError> {
+        let parts: Vec<&str> = code.trim().split('-').collect();
+        if parts.len() != PARAMS.len() {
+            return Err(CodeError::WrongLength(parts.len()));
+        }
+
+        let mut difficulty = Difficulty::EASY.custom();
+        for (&param, part) in PARAMS.iter().zip(parts) {
+            let value = part.parse::<u16>().map_err(|_| CodeError::NotANumber(part.to_string()))?;
+            let (min, max) = param.range();
+            if value < min || value > max {
+                return Err(CodeError::OutOfRange(param, value));
+            }
+            difficulty.set(param, value);
         }
+        Ok(difficulty)
     }
 }
@@ -31,13 +31,9 @@ impl Bomb {
                 return;
             };
 
-        world.entities.remove(&en_id);
-        for (i, entity) in world.entities.clone() {
-            let (x_, y_) = entity.get_pos();
-            let (dx, dy) = (x - x_, y - y_);
-            if dx * dx + dy * dy < BOMB_RADIUS * BOMB_RADIUS {
-                entity.get_hurt_fn()(world, i, 5);
-            }
+        world.remove_entity(en_id);
+        for i in world.entities_within((x, y), BOMB_RADIUS) {
+            world.hurt(i, 5, "Bomb");
         }
     }
 }
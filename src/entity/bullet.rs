@@ -4,7 +4,7 @@ use move_dir::MoveDir;
 use inventory::InventoryItem;
 use block;
 
-use super::{Entity, EntityWrapper};
+use super::{Entity, EntityWrapper, ItemDrop, collide};
 
 
 #[derive(PartialEq, Eq, Clone)]
@@ -60,11 +60,11 @@ impl Entity for Bullet {
 
         let mut new_pos_and_dir: Option<((u16, u16), (i8, i8))> = None;
 
-        if let Some(en) = world.entities.get_mut(&en_id) {
-            en.get_pos_mut().0 += dx as u16;
-            en.get_pos_mut().1 += dy as u16;
+        if let Some(pos) = world.entities.get(&en_id).map(|en| en.get_pos()) {
+            let new_pos = (pos.0.wrapping_add(dx as u16), pos.1.wrapping_add(dy as u16));
+            world.set_entity_pos(en_id, new_pos);
 
-            new_pos_and_dir = Some((en.get_pos().clone(), (dx, dy)));
+            new_pos_and_dir = Some((new_pos, (dx, dy)));
         }
 
 
@@ -75,54 +75,14 @@ impl Entity for Bullet {
                         .unwrap_or(true);
 
             if !passable {
-                world.blocks[pos.0 as usize][pos.1 as usize] = block::GROUND.clone();
-                world.entities.remove(&en_id);
+                world.set_block(pos, block::GROUND.clone());
+                world.remove_entity(en_id);
                 return true;
             } else {
-                let id = world.blocks.get(pos.0 as usize)
-                    .and_then(|x| x.get(pos.1 as usize))
-                    .map(|x| x.get_id())
-                    .unwrap_or(0);
-
-                let blkf = block::BLOCK_FUNCS.lock().unwrap();
-
-                match blkf.get(id) {
-                    Some(f) => {
-                        f(world, en_id);
-                    }
-                    None => {}
-                }
+                block::walk_on(world, en_id);
             }
-            for k in world.entities.clone().keys() {
-                if k != &en_id && world.entities.get(k).map(|x| x.get_pos()) == Some(pos) {
-                    let mut collided = false;
-
-                    let f = world.entities.get(k).unwrap().get_collision_fn();
-
-                    if !f(world, *k, en_id) {
-                        if let Some(en) = world.entities.get_mut(&en_id) {
-                            en.get_pos_mut().0 -= dir.0 as u16;
-                            en.get_pos_mut().1 -= dir.1 as u16;
-                            collided = true;
-                        }
-                    }
-
-
-                    if let Some(f) = world.entities.get(&en_id).map(|x| x.get_collision_fn()) {
-                        if !f(world, en_id, *k) {
-                            if let Some(en) = world.entities.get_mut(&en_id) {
-                                if !collided {
-                                    en.get_pos_mut().0 -= dir.0 as u16;
-                                    en.get_pos_mut().1 -= dir.1 as u16;
-                                }
-                                collided = true;
-                            }
-                        }
-                    }
-                    if collided {
-                        return false;
-                    }
-                }
+            if !collide(world, en_id, pos, dir) {
+                return false;
             }
         }
         true
@@ -133,18 +93,18 @@ impl Entity for Bullet {
 
         if let Some(enw) = world.entities.get_mut(&other_id) {
             match enw {
-                EntityWrapper::WPlayer(ref mut pl) => {
-                    pl.pick_up(InventoryItem::Bullet);
+                EntityWrapper::WPlayer(_) => {
+                    ItemDrop::give(world, other_id, InventoryItem::Bullet, 1);
                 }
                 EntityWrapper::WBomb(_) => {
                 }
                 _ => {
-                    enw.get_hurt_fn()(world, other_id, 1);
+                    world.hurt(other_id, 1, "Bullet");
                 }
             }
         }
 
-        world.entities.remove(&me_id);
+        world.remove_entity(me_id);
 
         false
     }
@@ -0,0 +1,88 @@
+use world::World;
+use shape::Shape;
+use inventory::InventoryItem;
+
+use super::{Entity, EntityWrapper};
+
+// How many ticks items stay on the ground, one minute
+pub const DESPAWN_TIME: u16 = 60 * 60;
+// They blink for the last five seconds
+const BLINK_TIME: u16 = 5 * 60;
+
+/// Items lying on the ground, picked up by walking onto them
+#[derive(PartialEq, Eq, Clone)]
+pub struct ItemDrop {
+    pub pos: (u16, u16),
+    pub item: InventoryItem,
+    pub count: u64,
+    // Ticks since it was dropped
+    pub age: u16,
+}
+
+impl ItemDrop {
+    pub fn new(pos: (u16, u16), item: InventoryItem, count: u64) -> ItemDrop {
+        ItemDrop {
+            pos: pos,
+            item: item,
+            count: count,
+            age: 0,
+        }
+    }
+
+    /// Give items to a player, what doesn't fit is dropped where they stand
+    pub fn give(world: &mut World, en_id: u64, item: InventoryItem, count: u64) {
+        let (pos, left) = match world.entities.get_mut(&en_id) {
+            Some(EntityWrapper::WPlayer(player)) => (player.pos, player.inventory.add(item.clone(), count)),
+            _ => return,
+        };
+        if left > 0 {
+            world.add_entity(EntityWrapper::WItemDrop(ItemDrop::new(pos, item, left)));
+        }
+    }
+}
+
+impl Entity for ItemDrop {
+    fn get_pos(&self) -> (u16, u16) { self.pos }
+    fn get_pos_mut(&mut self) -> &mut (u16, u16) { &mut self.pos }
+
+    fn get_shape(&self) -> Shape {
+        let Shape { ch, col, .. } = self.item.get_shape();
+        let blink_off = self.age + BLINK_TIME >= DESPAWN_TIME && (self.age / 15) % 2 == 1;
+        Shape::new(ch, col, if blink_off { (0, 0, 0) } else { (60, 60, 0) })
+    }
+
+    fn get_name(&self) -> String { format!("{} x{}", self.item.get_name(), self.count) }
+
+    fn tick(world: &mut World, en_id: u64) where Self: Sized {
+        let despawn = match world.entities.get_mut(&en_id) {
+            Some(EntityWrapper::WItemDrop(this)) => {
+                this.age += 1;
+                this.age >= DESPAWN_TIME
+            }
+            _ => return,
+        };
+        if despawn {
+            world.remove_entity(en_id);
+        }
+    }
+
+    fn on_collision(world: &mut World, me_id: u64, other_id: u64) -> bool
+        where Self: Sized {
+
+        let (item, count) = match world.entities.get(&me_id) {
+            Some(EntityWrapper::WItemDrop(this)) => (this.item.clone(), this.count),
+            _ => return true,
+        };
+        let left = match world.entities.get_mut(&other_id) {
+            Some(EntityWrapper::WPlayer(player)) => player.inventory.add(item, count),
+            _ => return true,
+        };
+
+        if left == 0 {
+            world.remove_entity(me_id);
+        } else if let Some(EntityWrapper::WItemDrop(this)) = world.entities.get_mut(&me_id) {
+            this.count = left;
+        }
+        true
+    }
+}
@@ -2,11 +2,13 @@ use world::{World, MetaAction};
 use shape::Shape;
 use ext::*;
 use move_dir::MoveDir;
+use pathfinding;
 
 use super::{Entity, EntityWrapper, Police};
 
 const SHOW_PATH_FINDING: bool = false;
 const PLAYER_SAFE_DIST: i32 = 50;
+const PATH_BUDGET: usize = 1000;
 
 #[derive(PartialEq, Eq, Clone)]
 pub struct Josef {
@@ -44,6 +46,7 @@ impl Entity for Josef {
     fn hurt(world: &mut World, en_id: u64, amount: u16) where Self: Sized {
         if let Some(EntityWrapper::WJosef(ref mut this)) = world.entities.get_mut(&en_id) {
             if this.health < amount {
+                this.health = 0;
                 world.do_metaaction(MetaAction::Win);
             }
             else {
@@ -68,7 +71,7 @@ impl Entity for Josef {
         if let Some(to_place) = pos_to_place {
             world.add_entity(
                 EntityWrapper::WPolice(
-                    Police::new(to_place, world.difficulty.get_police_speed(), world.difficulty.get_police_hurt_rate())
+                    Police::new(to_place, world.difficulty.police_speed, world.difficulty.police_hurt_rate)
                     )
                 );
         }
@@ -99,25 +102,24 @@ impl Entity for Josef {
         }
 
         if let Some(my_pos) = my_pos {
-            let heur = |(x, y): (u16, u16,)| {
-                let (dx, dy) = (x.wrapping_sub(player_pos.0), y.wrapping_sub(player_pos.1));
-                let (dx_sq, dy_sq) = (dx.saturating_mul(dx), dy.saturating_mul(dy));
-                let dist = dx_sq.saturating_add(dy_sq) as i32;
-                if dist > PLAYER_SAFE_DIST * PLAYER_SAFE_DIST {
-                    Some(-dist)
-                } else {
-                    Some(dist * 3)
-                }
+            // Keep about PLAYER_SAFE_DIST away from the player
+            let heur = |(x, y): (u16, u16)| {
+                let (dx, dy) = (x as i32 - player_pos.0 as i32, y as i32 - player_pos.1 as i32);
+                let dist = ((dx * dx + dy * dy) as f64).sqrt() as i32;
+                let off = (dist - PLAYER_SAFE_DIST).abs();
+                if off == 0 { None } else { Some(off as u32) }
             };
 
-            let path = world.find_path(
+            let path = pathfinding::find_path(
+                world,
                 my_pos,
+                |pos| world.is_blocked(pos),
                 |block, _|
                     if block.is_passable()
                         { Some(1) }
                         else { None },
                 heur,
-                1000);
+                PATH_BUDGET);
 
             if let Some(EntityWrapper::WJosef(ref mut this)) = world.entities.get_mut(&en_id) {
                 this.path = path;
@@ -126,7 +128,7 @@ impl Entity for Josef {
 
 
         if let Some(to_move) = to_move {
-            if !Josef::move_dir(world, en_id, to_move) || rand() < 0.25 {
+            if !Josef::move_dir(world, en_id, to_move) || world.rng.rand() < 0.25 {
                 if let Some(EntityWrapper::WJosef(ref mut this)) = world.entities.get_mut(&en_id) {
                     this.path = vec![];
                 }
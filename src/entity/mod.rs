@@ -10,11 +10,13 @@ mod josef;
 mod police;
 mod bomb;
 mod bullet;
+mod item_drop;
 pub use self::player::*;
 pub use self::josef::*;
 pub use self::police::*;
 pub use self::bomb::*;
 pub use self::bullet::*;
+pub use self::item_drop::*;
 
 pub trait Entity {
 
@@ -34,11 +36,11 @@ pub trait Entity {
 
         let mut new_pos_and_dir: Option<((u16, u16), (i8, i8))> = None;
 
-        if let Some(en) = world.entities.get_mut(&en_id) {
-            en.get_pos_mut().0 = en.get_pos_mut().0.wrapping_add(dx as u16);
-            en.get_pos_mut().1 = en.get_pos_mut().1.wrapping_add(dy as u16);
+        if let Some(pos) = world.entities.get(&en_id).map(|en| en.get_pos()) {
+            let new_pos = (pos.0.wrapping_add(dx as u16), pos.1.wrapping_add(dy as u16));
+            world.set_entity_pos(en_id, new_pos);
 
-            new_pos_and_dir = Some((en.get_pos().clone(), (dx, dy)));
+            new_pos_and_dir = Some((new_pos, (dx, dy)));
         }
 
 
@@ -49,56 +51,13 @@ pub trait Entity {
                         .unwrap_or(false);
 
             if passable {
-                let id = world.blocks.get(pos.0 as usize)
-                    .and_then(|x| x.get(pos.1 as usize))
-                    .map(|x| x.get_id())
-                    .unwrap_or(0);
-
-                let blkf = block::BLOCK_FUNCS.lock().unwrap();
-
-                match blkf.get(id) {
-                    Some(f) => {
-                        f(world, en_id);
-                    }
-                    None => {}
-                }
+                block::walk_on(world, en_id);
             } else {
-                if let Some(en) = world.entities.get_mut(&en_id) {
-                    en.get_pos_mut().0 = en.get_pos_mut().0.wrapping_sub(dx as u16);
-                    en.get_pos_mut().1 = en.get_pos_mut().1.wrapping_sub(dy as u16);
-                }
+                step_back(world, en_id, dir);
                 return false;
             }
-            for k in world.entities.clone().keys() {
-                if k != &en_id && world.entities.get(k).map(|x| x.get_pos()) == Some(pos) {
-                    let mut collided = false;
-
-                    let f = world.entities.get(k).unwrap().get_collision_fn();
-
-                    if !f(world, *k, en_id) {
-                        if let Some(en) = world.entities.get_mut(&en_id) {
-                            en.get_pos_mut().0 -= dir.0 as u16;
-                            en.get_pos_mut().1 -= dir.1 as u16;
-                            collided = true;
-                        }
-                    }
-
-
-                    if let Some(f) = world.entities.get(&en_id).map(|x| x.get_collision_fn()) {
-                        if !f(world, en_id, *k) {
-                            if let Some(en) = world.entities.get_mut(&en_id) {
-                                if !collided {
-                                    en.get_pos_mut().0 -= dir.0 as u16;
-                                    en.get_pos_mut().1 -= dir.1 as u16;
-                                }
-                                collided = true;
-                            }
-                        }
-                    }
-                    if collided {
-                        return false;
-                    }
-                }
+            if !collide(world, en_id, pos, dir) {
+                return false;
             }
         }
         true
@@ -179,11 +138,60 @@ macro_rules! MakeEntityWrapper {
     }
 }
 
+// Undo a step that couldn't be taken
+fn step_back(world: &mut World, en_id: u64, dir: (i8, i8)) {
+    if let Some(pos) = world.entities.get(&en_id).map(|en| en.get_pos()) {
+        world.set_entity_pos(en_id, (pos.0.wrapping_sub(dir.0 as u16), pos.1.wrapping_sub(dir.1 as u16)));
+    }
+}
+
+// Let an entity that just stepped onto pos collide with everything already there.
+// Returns false if something stopped it, in which case it has been moved back.
+fn collide(world: &mut World, en_id: u64, pos: (u16, u16), dir: (i8, i8)) -> bool {
+    for k in world.entities_at(pos) {
+        if k != en_id && world.entities.get(&k).map(|x| x.get_pos()) == Some(pos) {
+            // Items on the ground never get in the way, players just pick them up
+            if let Some(EntityWrapper::WItemDrop(_)) = world.entities.get(&k) {
+                ItemDrop::on_collision(world, k, en_id);
+                continue;
+            }
+
+            let mut collided = false;
+
+            let f = world.entities.get(&k).unwrap().get_collision_fn();
+
+            if !f(world, k, en_id) {
+                if world.entities.contains_key(&en_id) {
+                    step_back(world, en_id, dir);
+                    collided = true;
+                }
+            }
+
+
+            if let Some(f) = world.entities.get(&en_id).map(|x| x.get_collision_fn()) {
+                if !f(world, en_id, k) {
+                    if world.entities.contains_key(&en_id) {
+                        if !collided {
+                            step_back(world, en_id, dir);
+                        }
+                        collided = true;
+                    }
+                }
+            }
+            if collided {
+                return false;
+            }
+        }
+    }
+    true
+}
+
 MakeEntityWrapper!(
     Player=WPlayer,
     Josef=WJosef,
     Police=WPolice,
     Bomb=WBomb,
-    Bullet=WBullet
+    Bullet=WBullet,
+    ItemDrop=WItemDrop
     );
 
@@ -1,21 +1,22 @@
-use world::{World, HOTBAR_HEIGHT, MetaAction};
+use world::{World, Undo, HOTBAR_HEIGHT, MetaAction};
 use shape::Shape;
 use ext::*;
-use crafting::Recipe;
-use inventory::InventoryItem;
+use crafting::{Recipe, Missing};
+use inventory::{InventoryItem, Inventory, PLAYER_SLOTS};
 use move_dir::MoveDir;
 use block;
-use super::EntityWrapper;
+use super::{EntityWrapper, ItemDrop};
 
 use super::Entity;
 
 const COMMUNISM_WIDTH: u16 = 10;
+// How far bombs are thrown
+const THROW_DIST: u16 = 4;
 
 #[derive(PartialEq, Eq, Clone)]
 pub struct Player {
     pub pos: (u16, u16),
-    pub inventory: Vec<(InventoryItem, u64)>,
-    pub active: usize,
+    pub inventory: Inventory,
     pub hunger: u16
 }
 
@@ -24,42 +25,34 @@ impl Player {
     pub fn new(pos: (u16, u16), hunger: u16) -> Player {
         Player {
             pos: pos,
-            active: 0,
-            inventory: vec! [],
+            inventory: Inventory::new(PLAYER_SLOTS),
             hunger: hunger,
         }
     }
 
     pub fn place(world: &mut World, dir: MoveDir, en_id: u64) where Self: Sized {
-        let mut entity_positions = world.entities.clone().into_iter().map(|(_, x)| x.get_pos());
-
         let mut to_place: Option<(InventoryItem, (u16, u16))> = None;
 
-        if let Some(EntityWrapper::WPlayer(ref mut this)) = world.entities.get_mut(&en_id) {
-            let place_pos = (this.pos.0 + dir.to_vec().0 as u16, this.pos.1 + dir.to_vec().1 as u16);
-
-            if world.blocks
-                .get(place_pos.0 as usize)
-                    .and_then(|x| x.get(place_pos.1 as usize)) != Some(&block::GROUND)
-            {
-                return;
-            }
-
-            if entity_positions.any(|x| x == place_pos) {
-                return;
-            }
+        let place_pos = match world.entities.get(&en_id) {
+            Some(this) => dir.move_vec(this.get_pos()),
+            None => return,
+        };
 
-            if let Some((ref item, ref mut amount)) = this.inventory.get_mut(this.active) {
-                *amount -= 1;
+        if world.blocks
+            .get(place_pos.0 as usize)
+                .and_then(|x| x.get(place_pos.1 as usize)) != Some(&block::GROUND)
+        {
+            return;
+        }
 
-                to_place = Some((item.clone(), place_pos));
+        if world.is_blocked(place_pos) {
+            return;
+        }
 
-                if amount == &0 {
-                    this.inventory.remove(this.active);
-                    if this.active >= this.inventory.len() && this.inventory.len() > 0 {
-                        this.active = this.inventory.len() - 1;
-                    }
-                }
+        if let Some(EntityWrapper::WPlayer(ref mut this)) = world.entities.get_mut(&en_id) {
+            let active = this.inventory.active;
+            if let Some((item, _)) = this.inventory.take_slot(active, 1) {
+                to_place = Some((item, place_pos));
             }
         }
 
@@ -69,53 +62,142 @@ impl Player {
                 if let Some(EntityWrapper::WPlayer(ref mut this)) = world.entities.get_mut(&en_id) {
                     this.pick_up(to_place);
                 }
+            } else if let InventoryItem::Block(block) = to_place {
+                let player_pos = world.entities[&en_id].get_pos();
+                world.stats.blocks_placed += 1;
+                world.remember_undo(Undo {
+                    pos: (x, y),
+                    player_pos: player_pos,
+                    block: block,
+                    placed: true,
+                });
             }
         }
     }
 
-    pub fn pick_up(&mut self, item: InventoryItem) {
-        if let Some(&mut (_, ref mut count)) = self.inventory.iter_mut()
-                .find(|x| x.0 == item) {
-            *count += 1;
-        } else {
-            self.inventory.push((item, 1));
+    /// Use the item in the active slot: blocks are placed, bombs thrown, bullets fired and tools
+    /// switched on or off
+    pub fn use_active(world: &mut World, dir: MoveDir, en_id: u64) where Self: Sized {
+        let item = match world.entities.get(&en_id) {
+            Some(EntityWrapper::WPlayer(this)) => match this.inventory.active_slot() {
+                Some((item, _)) => item.clone(),
+                None => return,
+            },
+            _ => return,
+        };
+
+        match item {
+            InventoryItem::Block(_) | InventoryItem::Bullet => Player::place(world, dir, en_id),
+            InventoryItem::Bomb => Player::throw(world, dir, en_id),
+            InventoryItem::SuperBoots(_, _) | InventoryItem::Pickaxe(_, _) => {
+                if let Some(EntityWrapper::WPlayer(ref mut this)) = world.entities.get_mut(&en_id) {
+                    this.inventory.toggle_tool(&item.get_id());
+                }
+            }
         }
     }
 
-    pub fn craft(&mut self, rec: &Recipe) -> bool {
-
-        // Is craftable?
-        for (c_item, c_amount) in rec.needed.iter() {
-            // Has item?
-            if self.inventory
-                .iter()
-                .find(|(item, _)| item == c_item)
-                .map(|(_, amount)| amount < c_amount)
-                .unwrap_or(true)
-            {
-                return false;
+    // Bombs fly until they hit something or have gone THROW_DIST
+    fn throw(world: &mut World, dir: MoveDir, en_id: u64) where Self: Sized {
+        let mut pos = match world.entities.get(&en_id) {
+            Some(this) => this.get_pos(),
+            None => return,
+        };
+        let mut landed = None;
+        for _ in 0..THROW_DIST {
+            let next = dir.move_vec(pos);
+            let passable = world.blocks.get(next.0 as usize)
+                .and_then(|x| x.get(next.1 as usize))
+                .map(|x| x.is_passable())
+                .unwrap_or(false);
+            if !passable || world.is_blocked(next) {
+                break;
             }
+            pos = next;
+            landed = Some(pos);
+        }
+        let landed = match landed {
+            Some(pos) => pos,
+            None => return,
+        };
+
+        let thrown =
+            if let Some(EntityWrapper::WPlayer(ref mut this)) = world.entities.get_mut(&en_id) {
+                let active = this.inventory.active;
+                this.inventory.take_slot(active, 1)
+            } else {
+                None
+            };
+        if let Some((item, _)) = thrown {
+            item.place_pos(world, landed, dir);
         }
+    }
 
-        for (c_item, c_amount) in rec.needed.iter() {
-            let (i, (_, ref mut amount)) = self.inventory.iter_mut()
-                    .enumerate()
-                    .find(|(_, (item, _))| item == c_item)
-                    .expect("o no");
+    /// Returns false if there's no room for it
+    pub fn pick_up(&mut self, item: InventoryItem) -> bool {
+        self.inventory.add(item, 1) == 0
+    }
 
-            *amount -= c_amount;
+    /// Put the whole active stack on the ground next to the player
+    pub fn drop(world: &mut World, dir: MoveDir, en_id: u64) where Self: Sized {
+        let drop_pos = match world.entities.get(&en_id) {
+            Some(this) => dir.move_vec(this.get_pos()),
+            None => return,
+        };
+
+        let passable = world.blocks.get(drop_pos.0 as usize)
+            .and_then(|x| x.get(drop_pos.1 as usize))
+            .map(|x| x.is_passable())
+            .unwrap_or(false);
+        if !passable || world.is_blocked(drop_pos) {
+            return;
+        }
 
-            if amount == &0 {
-                self.inventory.remove(i);
-                if self.active >= self.inventory.len() && self.inventory.len() > 0 {
-                    self.active = self.inventory.len() - 1;
+        let dropped =
+            if let Some(EntityWrapper::WPlayer(ref mut this)) = world.entities.get_mut(&en_id) {
+                let active = this.inventory.active;
+                this.inventory.take_slot(active, u64::max_value())
+            } else {
+                None
+            };
+
+        if let Some((item, count)) = dropped {
+            // Onto the pile if the same thing is already lying there
+            for id in world.entities_at(drop_pos) {
+                if let Some(EntityWrapper::WItemDrop(other)) = world.entities.get_mut(&id) {
+                    if other.item == item {
+                        other.count += count;
+                        other.age = 0;
+                        return;
+                    }
                 }
             }
+            world.add_entity(EntityWrapper::WItemDrop(ItemDrop::new(drop_pos, item, count)));
+        }
+    }
+
+    /// Craft a recipe once, or say what's missing for it
+    pub fn craft(world: &mut World, en_id: u64, rec: &Recipe) -> Result<(), Vec<Missing>> where Self: Sized {
+        let missing = rec.missing(world, en_id);
+        if !missing.is_empty() {
+            return Err(missing);
         }
 
-        self.pick_up(rec.out.clone());
+        let this = match world.entities.get_mut(&en_id) {
+            Some(EntityWrapper::WPlayer(this)) => this,
+            _ => return Err(vec![]),
+        };
 
-        true
+        for (c_item, c_amount) in rec.needed.iter() {
+            this.inventory.take(c_item, *c_amount);
+        }
+
+        for (item, amount) in rec.out.iter() {
+            ItemDrop::give(world, en_id, item.clone(), *amount);
+            world.stats.items_crafted += *amount;
+        }
+
+        Ok(())
     }
 }
 
@@ -174,11 +256,22 @@ impl Entity for Player {
 
         let mut x = COMMUNISM_WIDTH + 1;
 
-        for (i, &(ref block, ref count)) in self.inventory.iter().enumerate() {
-            block.get_shape().draw((x, size.1 - 2));
+        for (i, &(ref item, ref count)) in self.inventory.slots().iter().enumerate() {
+            let shape = item.get_shape();
+            if item.durability().is_some() && !self.inventory.tool_on(&item.get_id()) {
+                // Switched off
+                Shape::new(shape.ch, (90, 90, 90), shape.bg).draw((x, size.1 - 2));
+            } else {
+                shape.draw((x, size.1 - 2));
+            }
             let text = format!("x{}", count);
 
-            if i == self.active {
+            // The digit that selects it
+            if i < 9 {
+                put_char((x, size.1 - 3), &Shape::new((b'1' + i as u8) as char, (120, 120, 120), (0, 0, 0)));
+            }
+
+            if i == self.inventory.active {
                 put_char((x, size.1 - 1), &Shape::new('^', (255, 255, 255), (0, 0, 0)));
             }
 
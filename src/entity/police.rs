@@ -4,6 +4,8 @@ use ext::*;
 use block;
 use move_dir::{MoveDir, DIRECTIONS};
 use inventory;
+use flow;
+use loot;
 
 use super::{Entity, EntityWrapper};
 
@@ -15,8 +17,8 @@ pub struct Police {
     pub walk_speed: u16,
     pub hurt_countdown: u16,
     pub hurt_speed: u16,
+    // The step taken last walk
     pub path: Vec<MoveDir>,
-    pub visited: Vec<(u16, u16)>,
     pub pos: (u16, u16),
 }
 
@@ -28,7 +30,6 @@ impl Police {
             hurt_countdown: 0,
             hurt_speed: hurt_speed,
             path: vec! [],
-            visited: vec! [],
             pos: pos
         }
     }
@@ -42,19 +43,20 @@ impl Entity for Police {
     fn get_name(&self) -> String { "Police".into() }
 
     fn hurt(world: &mut World, en_id: u64, _amount: u16) {
-        let mut drops: Vec<(u16, u16)> = Vec::new();
-
-        if let Some(en) = world.entities.get(&en_id) {
-            drops.push(en.get_pos());
-        }
+        let pos = match world.entities.get(&en_id) {
+            Some(en) => en.get_pos(),
+            None => return,
+        };
+        let mut drops: Vec<(u16, u16)> = vec![pos];
 
-        world.entities.remove(&en_id);
+        world.remove_entity(en_id);
+        world.stats.police_killed += 1;
 
         let mut i = 0;
         loop {
             if drops.is_empty() { break; }
 
-            let idx = (rand() * drops.len() as f64) as usize;
+            let idx = (world.rng.rand() * drops.len() as f64) as usize;
             let (x, y) = drops[idx];
             if inventory::InventoryItem::Block(block::COMMUNISM.clone()).place_pos(world, (x, y), MoveDir::Up) {
                 for dir in &DIRECTIONS {
@@ -63,12 +65,13 @@ impl Entity for Police {
                 i += 1;
             }
 
-            if i >= world.difficulty.get_communism_drop_rate() as usize ||
-                rand() < 1. / world.difficulty.get_communism_drop_rate() {
+            if i >= world.difficulty.communism_drop_rate as usize ||
+                world.rng.rand() < 1. / world.difficulty.communism_drop_rate as f64 {
                 break;
             }
         }
 
+        loot::drop_loot(world, "police", pos);
     }
 
     fn tick(world: &mut World, en_id: u64) where Self: Sized {
@@ -99,74 +102,50 @@ impl Entity for Police {
                 return;
             }
 
-            let mut visited = vec![ my_pos ];
-            let mut paths: Vec<(_, Vec<MoveDir>)> = vec![ (my_pos, vec![]) ];
-
-            let mut best_path = None;
-            let mut best_score = u16::max_value();
-
-            // Find closest path to player
-            'outer: for _ in 0..100 {
-                if let Some((ref pos, ref path)) = paths.clone().into_iter()
-                    .min_by_key(|&(ref pos, ref path)| {
-                        let (dx, dy) = (pos.0.wrapping_sub(player_pos.0), pos.1.wrapping_sub(player_pos.1));
-                        let (dx_sq, dy_sq) = (dx.saturating_mul(dx), dy.saturating_mul(dy));
-                        dx_sq.saturating_add(dy_sq).saturating_add(path.len() as u16 * 2)
-                    }) {
-                        paths.remove_item(&(*pos, path.clone()));
-
-                        let mut dirs = vec! [MoveDir::Up, MoveDir::Down, MoveDir::Left, MoveDir::Right];
-                        for _ in 0..4 {
-                            let fidx = rand() * dirs.len() as f64;
-                            let dir = dirs[fidx as usize];
-                            dirs.remove(fidx as usize);
-
-                            let (dx, dy) = dir.to_vec();
-                            let new_pos = (pos.0.wrapping_add(dx as u16), pos.1.wrapping_add(dy as u16));
-
-                            let mut new_path = path.clone();
-                            new_path.push(dir);
-
-                            if visited.contains(&new_pos) {
-                                continue;
-                            }
-                            if new_pos == player_pos {
-                                best_path = Some(new_path);
-                                break 'outer;
-                            }
-
-                            let passable_block = world.blocks.get(new_pos.0 as usize)
-                                .and_then(|x| x.get(new_pos.1 as usize))
-                                .map(|x| x.is_passable())
-                                .unwrap_or(false);
-                            let passable_entity =
-                                !world.entities.values()
-                                .any(|x| x.get_pos() == new_pos);
-
-                            if passable_block && passable_entity {
-                                paths.push((new_pos, new_path.clone()));
-                                visited.push(new_pos);
-
-                                let (dx, dy) = (pos.0.wrapping_sub(player_pos.0), pos.1.wrapping_sub(player_pos.1));
-                                let (dx_sq, dy_sq) = (dx.saturating_mul(dx), dy.saturating_mul(dy));
-                                let score = dx_sq.saturating_add(dy_sq).saturating_add(path.len() as u16 * 2);
-                                if score < best_score {
-                                    best_path = Some(new_path);
-                                    best_score = score;
-                                }
-                            }
-                        }
-                    } else {
-                        break 'outer;
-                    }
+            // Walk downhill on the flow field, picking randomly between equally good ways
+            let here = world.flow.distance(my_pos);
+            let mut best = vec![];
+            let mut best_dist = here;
+            for dir in &DIRECTIONS {
+                let dist = world.flow.distance(dir.move_vec(my_pos));
+                if dist < best_dist {
+                    best_dist = dist;
+                    best = vec![*dir];
+                } else if dist == best_dist && dist != here {
+                    best.push(*dir);
+                }
             }
 
-            if let Some(best_path) = best_path.clone() {
-                Police::move_dir(world, en_id, best_path[0]);
+            // The player is walled off, just get as close as possible
+            if here == flow::UNREACHABLE && best.is_empty() {
+                let dist_to_player = |pos: (u16, u16)|
+                    (pos.0 as i32 - player_pos.0 as i32).abs() + (pos.1 as i32 - player_pos.1 as i32).abs();
+
+                best = DIRECTIONS.iter()
+                    .cloned()
+                    .filter(|dir| {
+                        let new_pos = dir.move_vec(my_pos);
+                        let passable = world.blocks.get(new_pos.0 as usize)
+                            .and_then(|x| x.get(new_pos.1 as usize))
+                            .map(|x| x.is_passable())
+                            .unwrap_or(false);
+                        passable && dist_to_player(new_pos) < dist_to_player(my_pos)
+                    })
+                    .collect();
+            }
+
+            let path = if best.is_empty() {
+                vec![]
+            } else {
+                let idx = (world.rng.rand() * best.len() as f64) as usize;
+                vec![best[idx]]
+            };
+
+            if let Some(&dir) = path.first() {
+                Police::move_dir(world, en_id, dir);
             }
             if let Some(&mut EntityWrapper::WPolice(ref mut this)) = world.entities.get_mut(&en_id) {
-                this.path = best_path.unwrap_or(vec![]);
-                this.visited = visited;
+                this.path = path;
             }
         }
 
@@ -179,13 +158,8 @@ impl Entity for Police {
         if let Some(EntityWrapper::WPolice(ref mut me)) = world.entities.get_mut(&me_id) {
             if me.hurt_countdown == 0 {
                 me.hurt_countdown = me.hurt_speed;
-                if let Some(en) = world.entities.get(&other_id) {
-                    match en {
-                        EntityWrapper::WPlayer(_) => {
-                            en.get_hurt_fn()(world, other_id, 1);
-                        }
-                        _ => {}
-                    }
+                if let Some(EntityWrapper::WPlayer(_)) = world.entities.get(&other_id) {
+                    world.hurt(other_id, 1, "Police");
                 }
             } else {
                 me.hurt_countdown -= 1;
@@ -195,19 +169,22 @@ impl Entity for Police {
         false
     }
 
-    fn pre_draw(&self, _world: &World, _size: &(u16, u16), scroll: &(i16, i16)) {
+    fn pre_draw(&self, world: &World, _size: &(u16, u16), scroll: &(i16, i16)) {
         if SHOW_PATH_FINDING {
+            // Follow the flow field to the player
             let mut pos = self.get_pos();
 
-            for pos in &self.visited {
-                recolor((pos.0 - scroll.0 as u16, pos.1 - scroll.1 as u16), (0, 255, 0), (0, 0, 0));
-            }
+            loop {
+                let dist = world.flow.distance(pos);
+                let next = DIRECTIONS.iter()
+                    .map(|dir| dir.move_vec(pos))
+                    .find(|new_pos| world.flow.distance(*new_pos) < dist);
 
-            for dir in self.path.iter().skip(1) {
-                let (dx, dy) = dir.to_vec();
-                pos = (pos.0 + dx as u16, pos.1 + dy as u16);
-
-                recolor((pos.0 - scroll.0 as u16, pos.1 - scroll.1 as u16), (255, 0, 0), (0, 0, 0));
+                match next {
+                    Some(next) if dist != 0 => pos = next,
+                    _ => break,
+                }
+                recolor((pos.0.wrapping_sub(scroll.0 as u16), pos.1.wrapping_sub(scroll.1 as u16)), (255, 0, 0), (0, 0, 0));
             }
         }
     }
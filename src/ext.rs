@@ -1,28 +1,25 @@
 use shape::Shape;
+use host::with_host;
 
-use std::os::raw::c_char;
 use std::sync::Mutex;
 
-extern {
-    fn u_put_char(x: u16, y: u16, ch: usize, fr: u8, fg: u8, fb: u8, br: u8, bg: u8, bb: u8);
-    fn u_clear();
-    #[allow(unused)]
-    fn u_log(msg: c_char);
-    fn u_rand() -> f64;
+// Wrappers around the current host
+
+pub fn rand() -> f64 {
+    with_host(|host| host.rand()).unwrap_or(0.)
 }
 
-// Safe wrappers
+pub fn store(key: &str, value: &str) {
+    with_host(|host| host.store(key, value));
+}
 
-pub fn rand() -> f64 {
-    unsafe { u_rand() }
+pub fn load(key: &str) -> Option<String> {
+    with_host(|host| host.load(key)).and_then(|value| value)
 }
 
 #[cfg(debug_assertions)]
 pub fn log(x: &str) {
-    unsafe {
-        x.chars().for_each(|c| u_log(c as c_char));
-        u_log(10 as c_char); // Newline
-    }
+    with_host(|host| host.log(x));
 }
 
 #[cfg(not(debug_assertions))]
@@ -43,17 +40,14 @@ pub fn flip() {
             for (x, col) in unflipped.iter().enumerate() {
                 for (y, shape) in col.iter().enumerate() {
                     if Some(shape) != screen.get(x as usize).and_then(|col| col.get(y as usize)) {
-                        unsafe {
-                            u_put_char(
-                                x as u16, y as u16, shape.ch as usize,
-                                shape.col.0, shape.col.1, shape.col.2, shape.bg.0, shape.bg.1, shape.bg.2);
-                        }
+                        with_host(|host| host.put_char((x as u16, y as u16), shape));
                     }
                 }
             }
             *screen = unflipped.clone();
         }
     }
+    with_host(|host| host.flush());
 }
 
 pub fn put_char(pos: (u16, u16), shape: &Shape) {
@@ -94,16 +88,11 @@ pub fn recolor(pos: (u16, u16), fg: (u8, u8, u8), bg: (u8, u8, u8)) {
 
             screen[pos.0 as usize][pos.1 as usize].col = fg;
             screen[pos.0 as usize][pos.1 as usize].bg = bg;
-            unsafe {
-                // Put a space instead
-                u_put_char(pos.0, pos.1, current.ch as usize, fg.0, fg.1, fg.2, bg.0, bg.1, bg.2);
-            }
+            with_host(|host| host.put_char(pos, &Shape::new(current.ch, fg, bg)));
         }
         Err(_) => {
-            unsafe {
-                // Put a space instead
-                u_put_char(pos.0, pos.1, 32, fg.0, fg.1, fg.2, bg.0, bg.1, bg.2);
-            }
+            // Put a space instead
+            with_host(|host| host.put_char(pos, &Shape::new(' ', fg, bg)));
         }
     }
 }
@@ -115,7 +104,5 @@ pub fn clear() {
     if let Ok(ref mut unflipped) = UNFLIPPED.try_lock() {
         unflipped.clear();
     }
-    unsafe {
-        u_clear();
-    }
+    with_host(|host| host.clear());
 }
@@ -0,0 +1,165 @@
+// A flow field towards the player: how many steps every position is from them.
+//
+// Police just walk downhill on it, so one field is shared by all of them instead of every officer
+// searching for the player on their own. The field is rebuilt when the player moves and patched
+// when a single block changes.
+
+use block::Block;
+use move_dir::DIRECTIONS;
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
+use std::u32;
+
+pub const UNREACHABLE: u32 = u32::MAX;
+
+pub struct FlowField {
+    width: usize,
+    height: usize,
+    dist: Vec<u32>,
+    target: Option<(u16, u16)>,
+}
+
+impl FlowField {
+    pub fn new() -> FlowField {
+        FlowField {
+            width: 0,
+            height: 0,
+            dist: vec![],
+            target: None,
+        }
+    }
+
+    fn index(&self, pos: (u16, u16)) -> Option<usize> {
+        if (pos.0 as usize) < self.width && (pos.1 as usize) < self.height {
+            Some(pos.0 as usize * self.height + pos.1 as usize)
+        } else {
+            None
+        }
+    }
+
+    /// Steps from a position to the target, or UNREACHABLE
+    pub fn distance(&self, pos: (u16, u16)) -> u32 {
+        self.index(pos).map(|idx| self.dist[idx]).unwrap_or(UNREACHABLE)
+    }
+
+    pub fn target(&self) -> Option<(u16, u16)> { self.target }
+
+    /// Throw the field away, for when all blocks are replaced at once
+    pub fn invalidate(&mut self) {
+        self.target = None;
+    }
+
+    /// Make sure the field leads to target, rebuilding it if the target moved
+    pub fn update(&mut self, blocks: &[Vec<Block>], target: (u16, u16)) {
+        let height = blocks.get(0).map(|col| col.len()).unwrap_or(0);
+        if self.target == Some(target) && self.width == blocks.len() && self.height == height {
+            return;
+        }
+
+        self.width = blocks.len();
+        self.height = height;
+        self.dist = vec![UNREACHABLE; self.width * self.height];
+        self.target = Some(target);
+
+        let start = match self.index(target) {
+            Some(idx) => idx,
+            None => return,
+        };
+        self.dist[start] = 0;
+
+        // Every step costs the same, so a plain breadth first search does it
+        let mut queue = VecDeque::new();
+        queue.push_back(target);
+        while let Some(pos) = queue.pop_front() {
+            let next_dist = self.distance(pos) + 1;
+            for dir in &DIRECTIONS {
+                let new_pos = dir.move_vec(pos);
+                if let Some(idx) = self.index(new_pos) {
+                    if self.dist[idx] == UNREACHABLE && blocks[new_pos.0 as usize][new_pos.1 as usize].is_passable() {
+                        self.dist[idx] = next_dist;
+                        queue.push_back(new_pos);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Patch the field after the block at pos has changed
+    pub fn block_changed(&mut self, blocks: &[Vec<Block>], pos: (u16, u16)) {
+        let idx = match self.index(pos) {
+            Some(idx) => idx,
+            None => return,
+        };
+        if self.target.is_none() || self.target == Some(pos) {
+            return;
+        }
+
+        if blocks[pos.0 as usize][pos.1 as usize].is_passable() {
+            // Opened up, which can only make things closer
+            let dist = self.lowest_neighbour(pos).saturating_add(1);
+            if dist < self.dist[idx] {
+                self.dist[idx] = dist;
+                self.spread(blocks, vec![(dist, pos)]);
+            }
+        } else if self.dist[idx] != UNREACHABLE {
+            // Closed off. Everything that might have been reached through here is forgotten and
+            // then filled in again from its remaining neighbours.
+            let mut forgotten = vec![];
+            let mut queue = VecDeque::new();
+            queue.push_back((pos, self.dist[idx]));
+            self.dist[idx] = UNREACHABLE;
+
+            while let Some((at, dist)) = queue.pop_front() {
+                for dir in &DIRECTIONS {
+                    let new_pos = dir.move_vec(at);
+                    if let Some(new_idx) = self.index(new_pos) {
+                        if self.dist[new_idx] == dist + 1 {
+                            self.dist[new_idx] = UNREACHABLE;
+                            queue.push_back((new_pos, dist + 1));
+                            forgotten.push(new_pos);
+                        }
+                    }
+                }
+            }
+
+            let mut starts = vec![];
+            for at in forgotten {
+                let dist = self.lowest_neighbour(at);
+                if dist != UNREACHABLE {
+                    let idx = self.index(at).unwrap();
+                    self.dist[idx] = dist + 1;
+                    starts.push((dist + 1, at));
+                }
+            }
+            self.spread(blocks, starts);
+        }
+    }
+
+    fn lowest_neighbour(&self, pos: (u16, u16)) -> u32 {
+        DIRECTIONS.iter()
+            .map(|dir| self.distance(dir.move_vec(pos)))
+            .min()
+            .unwrap_or(UNREACHABLE)
+    }
+
+    // Lower distances outwards from some already updated positions
+    fn spread(&mut self, blocks: &[Vec<Block>], starts: Vec<(u32, (u16, u16))>) {
+        let mut heap: BinaryHeap<_> = starts.into_iter().map(Reverse).collect();
+
+        while let Some(Reverse((dist, pos))) = heap.pop() {
+            if dist > self.distance(pos) {
+                continue;
+            }
+            for dir in &DIRECTIONS {
+                let new_pos = dir.move_vec(pos);
+                if let Some(idx) = self.index(new_pos) {
+                    if dist + 1 < self.dist[idx] && blocks[new_pos.0 as usize][new_pos.1 as usize].is_passable() {
+                        self.dist[idx] = dist + 1;
+                        heap.push(Reverse((dist + 1, new_pos)));
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,92 @@
+// The best runs on each difficulty, kept by the host between runs.
+//
+// Each difficulty has its own key, holding one run per line as "won ticks seed" or
+// "lost ticks seed", best first. Custom difficulties are told apart by their code.
+
+use difficulty::{Difficulty, Preset};
+use stats;
+use ext;
+
+use std::cmp::Ordering;
+
+// How many runs are kept per difficulty
+pub const MAX_SCORES: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Score {
+    pub won: bool,
+    // How long the run took to win, or how long it lasted
+    pub ticks: u64,
+    pub seed: u32,
+}
+
+impl Score {
+    /// Wins beat losses. Faster wins are better, and losses that lasted longer.
+    pub fn better_than(&self, other: &Score) -> bool {
+        self.cmp_rank(other) == Ordering::Less
+    }
+
+    fn cmp_rank(&self, other: &Score) -> Ordering {
+        match (self.won, other.won) {
+            (true, false) => Ordering::Less,
+            (false, true) => Ordering::Greater,
+            (true, true) => self.ticks.cmp(&other.ticks),
+            (false, false) => other.ticks.cmp(&self.ticks),
+        }
+    }
+
+    /// Like "Won in 1:05, seed 12"
+    pub fn describe(&self) -> String {
+        let how = if self.won { "Won in" } else { "Lasted" };
+        format!("{} {}, seed {}", how, stats::format_ticks(self.ticks), self.seed)
+    }
+}
+
+fn key(difficulty: Difficulty) -> String {
+    match difficulty.preset {
+        Preset::Custom => format!("highscores_custom_{}", difficulty.code()),
+        preset => format!("highscores_{}", preset as u8),
+    }
+}
+
+pub fn parse_scores(text: &str) -> Vec<Score> {
+    text.lines()
+        .filter_map(|line| {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let won = match parts.get(0) {
+                Some(&"won") => true,
+                Some(&"lost") => false,
+                _ => return None,
+            };
+            Some(Score {
+                won: won,
+                ticks: parts.get(1)?.parse().ok()?,
+                seed: parts.get(2)?.parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+pub fn scores_to_string(scores: &[Score]) -> String {
+    scores.iter()
+        .map(|score| format!("{} {} {}\n", if score.won { "won" } else { "lost" }, score.ticks, score.seed))
+        .collect()
+}
+
+/// The best runs on a difficulty, best first
+pub fn scores(difficulty: Difficulty) -> Vec<Score> {
+    ext::load(&key(difficulty)).map(|text| parse_scores(&text)).unwrap_or(vec![])
+}
+
+/// Add a finished run. Returns its place in the table, from 0, if it's good enough to be kept.
+pub fn add(difficulty: Difficulty, score: Score) -> Option<usize> {
+    let mut scores = scores(difficulty);
+    let place = scores.iter().position(|other| score.better_than(other)).unwrap_or(scores.len());
+    if place >= MAX_SCORES {
+        return None;
+    }
+    scores.insert(place, score);
+    scores.truncate(MAX_SCORES);
+    ext::store(&key(difficulty), &scores_to_string(&scores));
+    Some(place)
+}
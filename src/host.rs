@@ -0,0 +1,214 @@
+use shape::Shape;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, TryLockError};
+
+/// Everything the game needs from whatever is running it: somewhere to draw characters, a way to
+/// clear that, somewhere to log to, a source of randomness and somewhere to keep things.
+pub trait Host {
+    fn put_char(&mut self, pos: (u16, u16), shape: &Shape);
+    fn clear(&mut self);
+    fn log(&mut self, msg: &str);
+    fn rand(&mut self) -> f64;
+
+    /// Called after every frame, for hosts that buffer their drawing
+    fn flush(&mut self) {}
+
+    /// Keep a value under a key, ideally for the next time the game is started too. By default
+    /// it's only kept in memory.
+    fn store(&mut self, key: &str, value: &str) {
+        store_in_memory(key, value);
+    }
+
+    /// The last value stored under a key
+    fn load(&mut self, key: &str) -> Option<String> {
+        load_from_memory(key)
+    }
+}
+
+lazy_static! {
+    static ref HOST: Mutex<Box<dyn Host + Send>> = Mutex::new(default_host());
+
+    // For hosts that can't keep things anywhere else
+    static ref MEMORY_STORE: Mutex<HashMap<String, String>> = Mutex::new(HashMap::new());
+}
+
+pub fn store_in_memory(key: &str, value: &str) {
+    MEMORY_STORE.lock().unwrap().insert(key.to_string(), value.to_string());
+}
+
+pub fn load_from_memory(key: &str) -> Option<String> {
+    MEMORY_STORE.lock().unwrap().get(key).cloned()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn default_host() -> Box<dyn Host + Send> {
+    Box::new(WasmHost)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn default_host() -> Box<dyn Host + Send> {
+    Box::new(MemoryHost::new())
+}
+
+/// Replace the host everything is drawn to
+pub fn set_host(host: Box<dyn Host + Send>) {
+    match HOST.lock() {
+        Ok(mut current) => *current = host,
+        Err(poisoned) => *poisoned.into_inner() = host,
+    }
+}
+
+/// Run something against the current host. Returns None if the host is already in use, i.e. when
+/// called again from inside `f`. A panic while talking to the host doesn't lock it for good.
+pub fn with_host<T>(f: impl FnOnce(&mut dyn Host) -> T) -> Option<T> {
+    match HOST.try_lock() {
+        Ok(mut host) => Some(f(&mut **host)),
+        Err(TryLockError::Poisoned(poisoned)) => Some(f(&mut **poisoned.into_inner())),
+        Err(TryLockError::WouldBlock) => None,
+    }
+}
+
+
+// The JavaScript host, see site/index.html
+
+#[cfg(target_arch = "wasm32")]
+mod wasm {
+    use std::os::raw::c_char;
+
+    extern {
+        pub fn u_put_char(x: u16, y: u16, ch: usize, fr: u8, fg: u8, fb: u8, br: u8, bg: u8, bb: u8);
+        pub fn u_clear();
+        pub fn u_log(msg: c_char);
+        pub fn u_rand() -> f64;
+        pub fn u_store(key: *const u8, key_len: usize, value: *const u8, value_len: usize);
+        // Puts the value in the buffer and returns its length, or -1 if there is none
+        pub fn u_load(key: *const u8, key_len: usize) -> i32;
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub struct WasmHost;
+
+#[cfg(target_arch = "wasm32")]
+impl Host for WasmHost {
+    fn put_char(&mut self, pos: (u16, u16), shape: &Shape) {
+        unsafe {
+            wasm::u_put_char(
+                pos.0, pos.1, shape.ch as usize,
+                shape.col.0, shape.col.1, shape.col.2, shape.bg.0, shape.bg.1, shape.bg.2);
+        }
+    }
+
+    fn clear(&mut self) {
+        unsafe { wasm::u_clear(); }
+    }
+
+    fn log(&mut self, msg: &str) {
+        use std::os::raw::c_char;
+        unsafe {
+            msg.chars().for_each(|c| wasm::u_log(c as c_char));
+            wasm::u_log(10 as c_char); // Newline
+        }
+    }
+
+    fn rand(&mut self) -> f64 {
+        unsafe { wasm::u_rand() }
+    }
+
+    fn store(&mut self, key: &str, value: &str) {
+        unsafe { wasm::u_store(key.as_ptr(), key.len(), value.as_ptr(), value.len()); }
+    }
+
+    fn load(&mut self, key: &str) -> Option<String> {
+        let len = unsafe { wasm::u_load(key.as_ptr(), key.len()) };
+        if len < 0 {
+            return None;
+        }
+        ::read_text(len as usize)
+    }
+}
+
+
+// In-memory host for running natively
+
+#[derive(Default)]
+pub struct MemoryState {
+    pub screen: Vec<Vec<Shape>>,
+    pub log: Vec<String>,
+    pub stored: HashMap<String, String>,
+    rand_state: u64,
+}
+
+/// A host that just remembers what was drawn and logged. Clones share the same state, so keep one
+/// around to look at the screen after handing the other to `set_host`.
+#[derive(Clone, Default)]
+pub struct MemoryHost {
+    pub state: Arc<Mutex<MemoryState>>,
+}
+
+impl MemoryHost {
+    pub fn new() -> MemoryHost {
+        MemoryHost::default()
+    }
+
+    /// The character drawn at a position, or a space if nothing is there
+    pub fn char_at(&self, pos: (u16, u16)) -> char {
+        self.state.lock().unwrap().screen
+            .get(pos.0 as usize)
+            .and_then(|col| col.get(pos.1 as usize))
+            .map(|sh| sh.ch)
+            .unwrap_or(' ')
+    }
+
+    /// One row of the screen as a string
+    pub fn row(&self, y: u16) -> String {
+        let state = self.state.lock().unwrap();
+        state.screen.iter()
+            .map(|col| col.get(y as usize).map(|sh| sh.ch).unwrap_or(' '))
+            .collect()
+    }
+}
+
+impl Host for MemoryHost {
+    fn put_char(&mut self, pos: (u16, u16), shape: &Shape) {
+        let mut state = self.state.lock().unwrap();
+        while state.screen.len() <= pos.0 as usize {
+            state.screen.push(vec![]);
+        }
+        while state.screen[pos.0 as usize].len() <= pos.1 as usize {
+            state.screen[pos.0 as usize].push(Shape::empty());
+        }
+        state.screen[pos.0 as usize][pos.1 as usize] = *shape;
+    }
+
+    fn clear(&mut self) {
+        self.state.lock().unwrap().screen.clear();
+    }
+
+    fn log(&mut self, msg: &str) {
+        self.state.lock().unwrap().log.push(msg.to_string());
+    }
+
+    // xorshift64*, so native runs don't need any outside source of randomness
+    fn rand(&mut self) -> f64 {
+        let mut state = self.state.lock().unwrap();
+        if state.rand_state == 0 {
+            state.rand_state = 0x2545_F491_4F6C_DD1D;
+        }
+        let mut x = state.rand_state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        state.rand_state = x;
+        (x.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn store(&mut self, key: &str, value: &str) {
+        self.state.lock().unwrap().stored.insert(key.to_string(), value.to_string());
+    }
+
+    fn load(&mut self, key: &str) -> Option<String> {
+        self.state.lock().unwrap().stored.get(key).cloned()
+    }
+}
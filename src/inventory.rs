@@ -1,4 +1,4 @@
-use block::{Block, GROUND};
+use block::{self, Block, GROUND};
 use world::World;
 use entity::{EntityWrapper, Bomb, Bullet};
 use shape::Shape;
@@ -7,7 +7,10 @@ use move_dir::MoveDir;
 pub const SUPER_BOOTS: InventoryItem = InventoryItem::SuperBoots(1000, 1001);
 pub const PICKAXE: InventoryItem = InventoryItem::Pickaxe(1000, 1001);
 
-#[derive(PartialEq, Eq, Clone)]
+// How many slots the player's inventory has
+pub const PLAYER_SLOTS: usize = 20;
+
+#[derive(PartialEq, Eq, Clone, Debug)]
 pub enum InventoryItem {
     Block(Block),
     Bomb,
@@ -18,16 +21,39 @@ pub enum InventoryItem {
 }
 
 impl InventoryItem {
+    /// Find an item by the id definition files use for it, like "bomb" or "block:wall"
+    pub fn by_id(id: &str) -> Option<InventoryItem> {
+        match id {
+            "bomb" => Some(InventoryItem::Bomb),
+            "bullet" => Some(InventoryItem::Bullet),
+            "super_boots" => Some(SUPER_BOOTS),
+            "pickaxe" => Some(PICKAXE),
+            _ if id.starts_with("block:") => block::by_id(&id["block:".len()..]).map(InventoryItem::Block),
+            _ => None,
+        }
+    }
+
+    pub fn get_id(&self) -> String {
+        match self {
+            InventoryItem::Block(ref block) => format!("block:{}", block.id),
+            InventoryItem::Bomb => "bomb".into(),
+            InventoryItem::Bullet => "bullet".into(),
+            InventoryItem::SuperBoots(_, _) => "super_boots".into(),
+            InventoryItem::Pickaxe(_, _) => "pickaxe".into(),
+        }
+    }
+
     pub fn place_pos(&self, world: &mut World, pos: (u16, u16), dir: MoveDir) -> bool {
         match self {
             InventoryItem::Block(ref block) => {
-                if let Some(last) =
-                    world.blocks.get_mut(pos.0 as usize).and_then(|x| x.get_mut(pos.1 as usize)) {
+                let on_ground = world.blocks.get(pos.0 as usize)
+                    .and_then(|x| x.get(pos.1 as usize))
+                    .map(|last| *last == GROUND.clone())
+                    .unwrap_or(false);
 
-                    if *last == GROUND.clone() {
-                        *last = block.clone();
-                        return true;
-                    }
+                if on_ground {
+                    world.set_block(pos, block.clone());
+                    return true;
                 }
             }
             InventoryItem::Bomb => {
@@ -43,6 +69,32 @@ impl InventoryItem {
         false
     }
 
+    /// How many of this fit in one inventory slot. Tools don't stack, so each keeps its own
+    /// durability.
+    pub fn max_stack(&self) -> u64 {
+        match self {
+            InventoryItem::SuperBoots(_, _) | InventoryItem::Pickaxe(_, _) => 1,
+            InventoryItem::Bomb => 10,
+            InventoryItem::Block(_) | InventoryItem::Bullet => 99,
+        }
+    }
+
+    /// (durability_left, max_durability) for tools
+    pub fn durability(&self) -> Option<(u16, u16)> {
+        match self {
+            InventoryItem::SuperBoots(left, max) | InventoryItem::Pickaxe(left, max) => Some((*left, *max)),
+            _ => None,
+        }
+    }
+
+    /// The same kind of item, however worn
+    pub fn same_kind(&self, other: &InventoryItem) -> bool {
+        match self.durability() {
+            Some(_) => self.get_id() == other.get_id(),
+            None => self == other,
+        }
+    }
+
     pub fn get_shape(&self) -> Shape {
         match self {
             InventoryItem::Block(ref block) => block.get_shape(),
@@ -73,8 +125,181 @@ impl InventoryItem {
             InventoryItem::Block(ref block) => block.desc.clone(),
             InventoryItem::Bomb => "Blows up enemies (and you)".into(),
             InventoryItem::Bullet => "Shoots things".into(),
-            InventoryItem::SuperBoots(_, _) => "Makes you able to run very fast. Ctrl+Alt+Arrow key to use".into(),
-            InventoryItem::Pickaxe(_, _) => "Makes you able to mine very fast. Alt+M+Arrow key to use".into(),
+            InventoryItem::SuperBoots(_, _) => "Makes you able to run very fast. Shift+Arrow key to use, E+Arrow key to switch on or off".into(),
+            InventoryItem::Pickaxe(_, _) => "Makes you able to mine very fast. Alt+M+Arrow key to use, E+Arrow key to switch on or off".into(),
+        }
+    }
+}
+
+/// Items in slots, each holding a stack of one kind of item
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Inventory {
+    slots: Vec<(InventoryItem, u64)>,
+    capacity: usize,
+    // The selected slot
+    pub active: usize,
+    // Ids of tools that are switched off, they aren't used until switched on again
+    tools_off: Vec<String>,
+}
+
+impl Inventory {
+    pub fn new(capacity: usize) -> Inventory {
+        Inventory {
+            slots: vec![],
+            capacity: capacity,
+            active: 0,
+            tools_off: vec![],
+        }
+    }
+
+    /// An inventory with some items already in it, as far as they fit
+    pub fn with_items(capacity: usize, items: Vec<(InventoryItem, u64)>) -> Inventory {
+        let mut inventory = Inventory::new(capacity);
+        for (item, count) in items {
+            inventory.add(item, count);
+        }
+        inventory
+    }
+
+    pub fn slots(&self) -> &[(InventoryItem, u64)] {
+        &self.slots
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn active_slot(&self) -> Option<&(InventoryItem, u64)> {
+        self.slots.get(self.active)
+    }
+
+    /// How many of an item there are in all slots, worn tools included
+    pub fn count(&self, item: &InventoryItem) -> u64 {
+        self.slots.iter()
+            .filter(|(other, _)| other.same_kind(item))
+            .map(|(_, count)| count)
+            .sum()
+    }
+
+    /// Put items in, topping up stacks of the same item before using new slots. Returns how many
+    /// didn't fit.
+    pub fn add(&mut self, item: InventoryItem, count: u64) -> u64 {
+        let max = item.max_stack();
+        let mut left = count;
+
+        for (other, other_count) in self.slots.iter_mut() {
+            if left == 0 {
+                break;
+            }
+            if *other == item && *other_count < max {
+                let moved = left.min(max - *other_count);
+                *other_count += moved;
+                left -= moved;
+            }
+        }
+        while left > 0 && self.slots.len() < self.capacity {
+            let moved = left.min(max);
+            self.slots.push((item.clone(), moved));
+            left -= moved;
+        }
+        left
+    }
+
+    /// Take up to count of an item out, worn tools included, emptying the last stacks first.
+    /// Returns how many were taken.
+    pub fn take(&mut self, item: &InventoryItem, count: u64) -> u64 {
+        let mut taken = 0;
+        for (other, other_count) in self.slots.iter_mut().rev() {
+            if taken == count {
+                break;
+            }
+            if other.same_kind(item) {
+                let moved = (count - taken).min(*other_count);
+                *other_count -= moved;
+                taken += moved;
+            }
+        }
+        self.remove_empty();
+        taken
+    }
+
+    /// Take up to count items out of one slot
+    pub fn take_slot(&mut self, idx: usize, count: u64) -> Option<(InventoryItem, u64)> {
+        let taken = {
+            let (item, slot_count) = self.slots.get_mut(idx)?;
+            let moved = count.min(*slot_count);
+            *slot_count -= moved;
+            (item.clone(), moved)
+        };
+        self.remove_empty();
+        Some(taken)
+    }
+
+    /// Use up one use of the first tool with an id, which breaks when it has none left. Returns
+    /// false if there is no such tool or it's switched off.
+    pub fn wear(&mut self, id: &str) -> bool {
+        if !self.tool_on(id) {
+            return false;
+        }
+        let idx = match self.slots.iter().position(|(item, _)| item.durability().is_some() && item.get_id() == id) {
+            Some(idx) => idx,
+            None => return false,
+        };
+        let broken = match self.slots[idx].0 {
+            InventoryItem::SuperBoots(ref mut left, _) | InventoryItem::Pickaxe(ref mut left, _) => {
+                *left = left.saturating_sub(1);
+                *left == 0
+            }
+            _ => false,
+        };
+        if broken {
+            self.take_slot(idx, 1);
+        }
+        true
+    }
+
+    /// Switch the tools with an id on or off. Returns whether they are on now.
+    pub fn toggle_tool(&mut self, id: &str) -> bool {
+        match self.tools_off.iter().position(|off| off == id) {
+            Some(idx) => { self.tools_off.remove(idx); true }
+            None => { self.tools_off.push(id.to_string()); false }
+        }
+    }
+
+    pub fn tool_on(&self, id: &str) -> bool {
+        !self.tools_off.iter().any(|off| off == id)
+    }
+
+    pub fn tools_off(&self) -> &[String] {
+        &self.tools_off
+    }
+
+    /// Make a slot the active one, if there is anything in it
+    pub fn select(&mut self, idx: usize) {
+        if idx < self.slots.len() {
+            self.active = idx;
+        }
+    }
+
+    pub fn select_next(&mut self) {
+        if self.active + 1 < self.slots.len() {
+            self.active += 1;
+        }
+    }
+
+    pub fn select_prev(&mut self) {
+        if self.active > 0 {
+            self.active -= 1;
+        }
+    }
+
+    fn remove_empty(&mut self) {
+        // Stay on the same stack when the slots before it go
+        let active = self.active.min(self.slots.len());
+        self.active -= self.slots[..active].iter().filter(|(_, count)| *count == 0).count();
+        self.slots.retain(|(_, count)| *count > 0);
+        if self.active >= self.slots.len() && self.slots.len() > 0 {
+            self.active = self.slots.len() - 1;
         }
     }
 }
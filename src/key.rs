@@ -7,9 +7,45 @@ pub enum Key {
     Arrow(MoveDir),
     Letter(u8),
     Digit(u8),
+    // F1 to F12
+    F(u8),
+    // The digits on the numpad
+    Numpad(u8),
     Shift, Alt, Ctrl, Meta,
-    Enter, Backspace, Tab,
-    Plus, Minus
+    Enter, Backspace, Tab, Escape, Space,
+    Plus, Minus, Equals,
+    Comma, Period, Slash, Backslash, Semicolon, Quote, Backquote, BracketLeft, BracketRight,
+}
+
+// Keys without a number in them, by the names parse_name understands
+const NAMED: &[(Key, &str)] = &[
+    (Key::Shift, "Shift"), (Key::Alt, "Alt"), (Key::Ctrl, "Ctrl"), (Key::Meta, "Meta"),
+    (Key::Enter, "Enter"), (Key::Backspace, "Backspace"), (Key::Tab, "Tab"),
+    (Key::Escape, "Escape"), (Key::Space, "Space"),
+    (Key::Plus, "Plus"), (Key::Minus, "Minus"), (Key::Equals, "Equals"),
+    (Key::Comma, "Comma"), (Key::Period, "Period"), (Key::Slash, "Slash"),
+    (Key::Backslash, "Backslash"), (Key::Semicolon, "Semicolon"), (Key::Quote, "Quote"),
+    (Key::Backquote, "Backquote"), (Key::BracketLeft, "BracketLeft"), (Key::BracketRight, "BracketRight"),
+];
+
+impl Key {
+    /// The key for a letter, like Key::letter('M')
+    pub fn letter(ch: char) -> Key {
+        Key::Letter(ch.to_ascii_uppercase() as u8 - b'A')
+    }
+
+    /// A name for the key that parse_name understands, used when saving key bindings
+    pub fn name(&self) -> String {
+        match self {
+            Key::Arrow(dir) => format!("{:?}", dir),
+            Key::Letter(_) | Key::Digit(_) | Key::F(_) => self.to_string(),
+            Key::Numpad(d) => format!("Numpad{}", d),
+            _ => NAMED.iter()
+                .find(|(key, _)| key == self)
+                .map(|(_, name)| name.to_string())
+                .unwrap_or_default(),
+        }
+    }
 }
 
 impl Display for Key {
@@ -18,6 +54,8 @@ impl Display for Key {
             Key::Arrow(dir) => dir.to_ch().to_string(),
             Key::Letter(ch) => format!("{}", (ch + 'A' as u8) as char),
             Key::Digit(ch)  => format!("{}", (ch + '0' as u8) as char),
+            Key::F(n)       => format!("F{}", n),
+            Key::Numpad(n)  => format!("Num{}", n),
             Key::Shift      => "Shift".into(),
             Key::Alt        => "Alt".into(),
             Key::Ctrl       => "Ctrl".into(),
@@ -25,13 +63,117 @@ impl Display for Key {
             Key::Enter      => "Enter".into(),
             Key::Backspace  => "Backspace".into(),
             Key::Tab        => "Tab".into(),
+            Key::Escape     => "Esc".into(),
+            Key::Space      => "Space".into(),
             Key::Plus       => "Plus".into(),
             Key::Minus      => "Minus".into(),
+            Key::Equals     => "=".into(),
+            Key::Comma      => ",".into(),
+            Key::Period     => ".".into(),
+            Key::Slash      => "/".into(),
+            Key::Backslash  => "\\".into(),
+            Key::Semicolon  => ";".into(),
+            Key::Quote      => "'".into(),
+            Key::Backquote  => "`".into(),
+            Key::BracketLeft  => "[".into(),
+            Key::BracketRight => "]".into(),
         };
         write!(fmt, "{}", text)
     }
 }
 
+pub fn parse_name(name: &str) -> Option<Key> {
+    let key = match name {
+        "Up"        => Key::Arrow(MoveDir::Up),
+        "Down"      => Key::Arrow(MoveDir::Down),
+        "Left"      => Key::Arrow(MoveDir::Left),
+        "Right"     => Key::Arrow(MoveDir::Right),
+        _ => {
+            if let Some((key, _)) = NAMED.iter().find(|(_, other)| *other == name) {
+                return Some(key.clone());
+            }
+            if name.starts_with("Numpad") {
+                return digit(&name["Numpad".len()..]).map(Key::Numpad);
+            }
+            if name.starts_with('F') && name.len() > 1 {
+                return f_key(&name[1..]);
+            }
+            let mut chars = name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(ch @ 'A'...'Z'), None) => Key::letter(ch),
+                (Some(ch @ '0'...'9'), None) => Key::Digit(ch as u8 - b'0'),
+                _ => return None,
+            }
+        }
+    };
+    Some(key)
+}
+
+/// Turn a KeyboardEvent.code into a key. Codes are the place on the keyboard rather than what is
+/// printed on it, so WASD is in the same place on any layout.
+pub fn parse_code(code: &str) -> Option<Key> {
+    let key = match code {
+        "ArrowUp"    => Key::Arrow(MoveDir::Up),
+        "ArrowDown"  => Key::Arrow(MoveDir::Down),
+        "ArrowLeft"  => Key::Arrow(MoveDir::Left),
+        "ArrowRight" => Key::Arrow(MoveDir::Right),
+        "ShiftLeft" | "ShiftRight"     => Key::Shift,
+        "AltLeft" | "AltRight"         => Key::Alt,
+        "ControlLeft" | "ControlRight" => Key::Ctrl,
+        "MetaLeft" | "MetaRight" | "OSLeft" | "OSRight" => Key::Meta,
+        "Enter" | "NumpadEnter"        => Key::Enter,
+        "Backspace"    => Key::Backspace,
+        "Tab"          => Key::Tab,
+        "Escape"       => Key::Escape,
+        "Space"        => Key::Space,
+        "NumpadAdd"    => Key::Plus,
+        "Minus" | "NumpadSubtract" => Key::Minus,
+        "Equal"        => Key::Equals,
+        "Comma"        => Key::Comma,
+        "Period"       => Key::Period,
+        "Slash"        => Key::Slash,
+        "Backslash"    => Key::Backslash,
+        "Semicolon"    => Key::Semicolon,
+        "Quote"        => Key::Quote,
+        "Backquote"    => Key::Backquote,
+        "BracketLeft"  => Key::BracketLeft,
+        "BracketRight" => Key::BracketRight,
+        _ => {
+            if code.starts_with("Key") && code.len() == 4 {
+                let ch = code.as_bytes()[3] as char;
+                return if ch.is_ascii_uppercase() { Some(Key::letter(ch)) } else { None };
+            }
+            if code.starts_with("Digit") {
+                return digit(&code["Digit".len()..]).map(Key::Digit);
+            }
+            if code.starts_with("Numpad") {
+                return digit(&code["Numpad".len()..]).map(Key::Numpad);
+            }
+            if code.starts_with('F') && code.len() > 1 {
+                return f_key(&code[1..]);
+            }
+            return None;
+        }
+    };
+    Some(key)
+}
+
+fn digit(text: &str) -> Option<u8> {
+    match text.as_bytes() {
+        [d @ b'0'...b'9'] => Some(d - b'0'),
+        _ => None,
+    }
+}
+
+fn f_key(number: &str) -> Option<Key> {
+    match number.parse::<u8>() {
+        Ok(n @ 1...12) => Some(Key::F(n)),
+        _ => None,
+    }
+}
+
+/// Turn a KeyboardEvent.keyCode into a key. Some keys have different codes in different browsers,
+/// those all map to the same key.
 pub fn parse_key(code: u8) -> Option<Key> {
     match code {
         37 => Some(Key::Arrow(MoveDir::Left)),
@@ -41,14 +183,28 @@ pub fn parse_key(code: u8) -> Option<Key> {
         17 => Some(Key::Ctrl),
         16 => Some(Key::Shift),
         18 => Some(Key::Alt),
-        224 => Some(Key::Meta),
+        91 | 92 | 224 => Some(Key::Meta),
         13 => Some(Key::Enter),
         8 => Some(Key::Backspace),
         9 => Some(Key::Tab),
-        171 => Some(Key::Plus),
-        173 => Some(Key::Minus),
+        27 => Some(Key::Escape),
+        32 => Some(Key::Space),
+        107 | 171 => Some(Key::Plus),
+        109 | 173 | 189 => Some(Key::Minus),
+        61 | 187 => Some(Key::Equals),
+        188 => Some(Key::Comma),
+        190 => Some(Key::Period),
+        191 => Some(Key::Slash),
+        220 => Some(Key::Backslash),
+        59 | 186 => Some(Key::Semicolon),
+        222 => Some(Key::Quote),
+        192 => Some(Key::Backquote),
+        219 => Some(Key::BracketLeft),
+        221 => Some(Key::BracketRight),
         65...90 => Some(Key::Letter(code - b'A')),
         48...57 => Some(Key::Digit(code - 48)),
+        96...105 => Some(Key::Numpad(code - 96)),
+        112...123 => Some(Key::F(code - 111)),
         _ => None
     }
 }
@@ -1,4 +1,4 @@
-#![feature(vec_remove_item, nll, const_let)]
+#![feature(nll, const_let)]
 
 #[macro_use]
 extern crate lazy_static;
@@ -6,26 +6,43 @@ extern crate textwrap;
 
 use textwrap::Wrapper;
 
-mod ext;
-mod key;
-mod world;
-mod controls;
-mod block;
-mod entity;
-mod shape;
-mod difficulty;
-mod crafting;
-mod inventory;
-mod move_dir;
+pub mod host;
+pub mod ext;
+pub mod key;
+pub mod world;
+pub mod controls;
+pub mod block;
+pub mod entity;
+pub mod shape;
+pub mod difficulty;
+pub mod crafting;
+pub mod loot;
+pub mod inventory;
+pub mod move_dir;
+pub mod rng;
+pub mod pathfinding;
+pub mod flow;
+pub mod spatial;
+pub mod defs;
+pub mod sim;
+pub mod save;
+pub mod stats;
+pub mod highscores;
+mod replay;
 
 use world::*;
-use difficulty::Difficulty;
+use difficulty::{Difficulty, Preset, PARAMS};
 use shape::Shape;
 use move_dir::MoveDir;
+use replay::{Recording, Replay, KeyEvent};
+use defs::DefError;
+use inventory::Inventory;
+use stats::Stats;
 
 use std::sync::Mutex;
 use std::sync::mpsc::{Receiver, channel};
 use std::collections::HashSet;
+#[cfg(target_arch = "wasm32")]
 use std::panic::set_hook;
 
 const TITLE: &str = "☭☭☭ COMMUNISM SIMULATOR ☭☭☭";
@@ -33,8 +50,16 @@ const INVENTORY_TITLE: &str = "☭☭☭ INVENTORY ☭☭☭";
 const INVENTORY_INVENTORY: &str = "Your Inventory";
 const INVENTORY_CRAFTING: &str = "Crafting";
 const INVENTORY_INDENT: u16 = 3;
-
-const WORLD_SIZE: (usize, usize) = (180, 111);
+const BINDINGS_TITLE: &str = "☭☭☭ CONTROLS ☭☭☭";
+const PAUSE_TITLE: &str = "☭☭☭ PAUSED ☭☭☭";
+const EDITOR_TITLE: &str = "☭☭☭ CUSTOM DIFFICULTY ☭☭☭";
+// Where the host keeps the code of the last custom difficulty
+const CUSTOM_DIFFICULTY_KEY: &str = "custom_difficulty";
+// How many of the high scores fit next to the menu
+const MENU_SCORES: usize = 5;
+const PAUSE_OPTIONS: &[&str] = &["Resume", "Restart with the same seed", "Save", "Controls", "Quit to the menu"];
+const CRAFT_HAVE_COLOR: (u8, u8, u8) = (100, 255, 100);
+const CRAFT_MISSING_COLOR: (u8, u8, u8) = (255, 100, 100);
 
 struct Game {
     state: GameState,
@@ -43,11 +68,237 @@ struct Game {
 
 enum GameState {
     Playing(WorldWrapper),
-    Menu(Difficulty),
-    GameOver(Difficulty, RestartMessage),
+    Menu(MenuState),
+    // The controls screen and the menu to go back to
+    Bindings(BindingsState, MenuState),
+    // The custom difficulty editor and the menu to go back to
+    Editor(EditorState, MenuState),
+    Paused(WorldWrapper, PauseState),
+    GameOver(GameOverState),
+}
+
+#[derive(Clone)]
+struct MenuState {
+    difficulty: Difficulty,
+    // Picked by going past the hardest preset
+    custom: Difficulty,
+    seed: u32,
+    // Has the player started typing in their own seed?
+    typed_seed: bool,
+    // Of the picked difficulty, loaded when it's picked rather than every frame
+    scores: Vec<highscores::Score>,
+}
+
+impl MenuState {
+    fn new(difficulty: Difficulty) -> MenuState {
+        let custom = if difficulty.preset == Preset::Custom {
+            difficulty
+        } else {
+            ext::load(CUSTOM_DIFFICULTY_KEY)
+                .and_then(|code| Difficulty::from_code(&code).ok())
+                .unwrap_or(Difficulty::MEDIUM.custom())
+        };
+        MenuState {
+            difficulty: difficulty,
+            custom: custom,
+            seed: random_seed(),
+            typed_seed: false,
+            scores: highscores::scores(difficulty),
+        }
+    }
+
+    fn set_difficulty(&mut self, difficulty: Difficulty) {
+        self.difficulty = difficulty;
+        self.scores = highscores::scores(difficulty);
+    }
+
+    fn use_custom(&mut self, custom: Difficulty) {
+        self.custom = custom;
+        self.set_difficulty(custom);
+        ext::store(CUSTOM_DIFFICULTY_KEY, &custom.code());
+    }
+}
+
+// The end of a run
+struct GameOverState {
+    difficulty: Difficulty,
+    seed: u32,
+    msg: RestartMessage,
+    stats: Stats,
+    // The run's place on the high scores, if it made it there
+    place: Option<usize>,
+    // Loaded once when the run ends
+    scores: Vec<highscores::Score>,
+}
+
+// Changing the values of a custom difficulty
+struct EditorState {
+    difficulty: Difficulty,
+    // Index into difficulty::PARAMS
+    selected: usize,
+    // A code being typed in, None if not typing
+    code: Option<String>,
+    // What happened with the last code
+    message: Option<(String, bool)>,
+}
+
+impl EditorState {
+    fn new(difficulty: Difficulty) -> EditorState {
+        EditorState {
+            difficulty: difficulty.custom(),
+            selected: 0,
+            code: None,
+            message: None,
+        }
+    }
+
+    /// Returns true when going back
+    fn key_up(&mut self, key: key::Key) -> bool {
+        let param = PARAMS[self.selected];
+        let value = self.difficulty.get(param);
+
+        match key {
+            key::Key::Arrow(MoveDir::Up) if self.selected > 0 => { self.selected -= 1; }
+            key::Key::Arrow(MoveDir::Down) if self.selected + 1 < PARAMS.len() => { self.selected += 1; }
+            key::Key::Arrow(MoveDir::Left) => { self.difficulty.set(param, value.saturating_sub(param.step())); }
+            key::Key::Arrow(MoveDir::Right) => { self.difficulty.set(param, value.saturating_add(param.step())); }
+            key::Key::Digit(d) | key::Key::Numpad(d) => {
+                self.code.get_or_insert(String::new()).push((b'0' + d) as char);
+            }
+            key::Key::Minus => {
+                self.code.get_or_insert(String::new()).push('-');
+            }
+            key::Key::Backspace => {
+                if let Some(mut code) = self.code.take() {
+                    code.pop();
+                    if !code.is_empty() {
+                        self.code = Some(code);
+                    }
+                }
+            }
+            key::Key::Enter if self.code.is_some() => {
+                let code = self.code.take().unwrap_or_default();
+                self.message = Some(match Difficulty::from_code(&code) {
+                    Ok(difficulty) => {
+                        self.difficulty = difficulty;
+                        ("Code loaded".to_string(), true)
+                    }
+                    Err(e) => (e.to_string(), false),
+                });
+            }
+            key::Key::Escape if self.code.is_some() => { self.code = None; }
+            key::Key::Enter | key::Key::Escape => { return true; }
+            _ if key == key::Key::letter('D') => { return true; }
+            _ => {}
+        }
+        false
+    }
+}
+
+// Changing the key bindings, from the menu or while paused
+struct BindingsState {
+    // Index into controls::bindings()
+    selected: usize,
+    // The keys held so far while waiting for a new binding, None if not waiting
+    new_keys: Option<Vec<key::Key>>,
+    // What happened with the last change
+    message: Option<(String, bool)>,
+}
+
+impl BindingsState {
+    fn new() -> BindingsState {
+        BindingsState {
+            selected: 0,
+            new_keys: None,
+            message: None,
+        }
+    }
+
+    fn key_down(&mut self, key: key::Key) {
+        if let Some(ref mut held) = self.new_keys {
+            if !held.contains(&key) {
+                held.push(key);
+            }
+        }
+    }
+
+    /// Returns true when going back
+    fn key_up(&mut self, key: key::Key) -> bool {
+        let bindings = controls::bindings();
+        let action = bindings[self.selected].0;
+
+        match self.new_keys.take() {
+            // The first key let go of is bound, with the others that are still held as modifiers
+            Some(mut held) => {
+                if !held.contains(&key) {
+                    self.new_keys = Some(held);
+                    return false;
+                }
+                held.retain(|k| *k != key);
+                if (key == key::Key::Backspace || key == key::Key::Escape) && held.is_empty() {
+                    self.message = None;
+                } else {
+                    self.message = Some(rebind(action, controls::Binding::new(held, key)));
+                }
+            }
+            None => match key {
+                key::Key::Arrow(MoveDir::Up) if self.selected > 0 => {
+                    self.selected -= 1;
+                    self.message = None;
+                }
+                key::Key::Arrow(MoveDir::Down) if self.selected + 1 < bindings.len() => {
+                    self.selected += 1;
+                    self.message = None;
+                }
+                key::Key::Enter => {
+                    self.new_keys = Some(vec![]);
+                    self.message = None;
+                }
+                key::Key::Backspace => {
+                    let default = controls::default_bindings().into_iter().find(|(other, _)| *other == action);
+                    if let Some((_, binding)) = default {
+                        self.message = Some(rebind(action, binding));
+                    }
+                }
+                key::Key::Letter(10) | key::Key::Escape => { return true; } // K
+                _ => {}
+            }
+        }
+        false
+    }
+}
+
+// The overlay over a paused run
+struct PauseState {
+    // Index into PAUSE_OPTIONS
+    selected: usize,
+    // The controls screen, if it was opened from here
+    controls: Option<BindingsState>,
+    // Has restart been picked once already?
+    confirm_restart: bool,
+    message: Option<(String, bool)>,
 }
 
-#[derive(Clone, Copy)]
+impl PauseState {
+    fn new() -> PauseState {
+        PauseState {
+            selected: 0,
+            controls: None,
+            confirm_restart: false,
+            message: None,
+        }
+    }
+}
+
+/// Bind an action to new keys, returning what to tell the player and if it worked
+fn rebind(action: controls::Action, binding: controls::Binding) -> (String, bool) {
+    match controls::rebind(action, binding.clone()) {
+        Ok(()) => (format!("{} is now {}", action.desc(), binding), true),
+        Err(other) => (format!("{} would get in the way of {}", binding, other.desc()), false),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
 enum RestartMessage {
     Died, Won
 }
@@ -56,28 +307,423 @@ struct WorldWrapper {
     world: World,
     action_receiver: Receiver<MetaAction>,
     keys_down: HashSet<key::Key>,
-    at_inventory: Option<AtInventory>
+    // The bindings the run started with, so replays use the same ones
+    bindings: Vec<(controls::Action, controls::Binding)>,
+    at_inventory: Option<AtInventory>,
+    // Was the last action Die? It has to be done twice in a row.
+    confirm_die: bool,
+    // What happened with the last action, shown until the next one
+    message: Option<(String, bool)>,
+    // How many times tick has been called during this run
+    frame: u64,
+    // None if the run can't be replayed from its seed, like a run continued from a save
+    recording: Option<Recording>,
+    replay: Option<Replay>,
+}
+
+impl WorldWrapper {
+    fn new(world: World, action_receiver: Receiver<MetaAction>) -> WorldWrapper {
+        WorldWrapper {
+            world: world,
+            action_receiver: action_receiver,
+            keys_down: HashSet::new(),
+            bindings: controls::bindings(),
+            at_inventory: None,
+            confirm_die: false,
+            message: None,
+            frame: 0,
+            recording: None,
+            replay: None,
+        }
+    }
+
+    fn key_down(&mut self, key: key::Key) {
+        if let Some(ref mut rec) = self.recording {
+            rec.record(self.frame, KeyEvent::Down, key.clone());
+        }
+
+        if let Some(action) = controls::parse_control(&self.bindings, &key, &self.keys_down) {
+            ext::log(&format!("Action: {:?}", action));
+            let confirmed = self.confirm_die;
+            self.confirm_die = false;
+            self.message = None;
+            if let controls::Action::ToggleInventory = action {
+                if self.at_inventory.is_some() {
+                    self.at_inventory = None;
+                } else {
+                    self.at_inventory = Some(AtInventory::new());
+                }
+            }
+            if self.at_inventory.is_none() {
+                match action {
+                    // A held key repeating isn't pressing it again
+                    controls::Action::Die if !confirmed || self.keys_down.contains(&key) => {
+                        let keys = self.bindings.iter()
+                            .find(|(other, _)| *other == action)
+                            .map(|(_, binding)| binding.to_string())
+                            .unwrap_or_default();
+                        self.confirm_die = true;
+                        self.message = Some((format!("Press {} again to die, anything else to keep going", keys), false));
+                    }
+                    controls::Action::Undo => {
+                        self.message = Some(match self.world.undo() {
+                            Ok(()) => ("Undone".into(), true),
+                            Err(e) => (e.to_string(), false),
+                        });
+                        self.world.auto_walk = vec![];
+                        self.world.auto_mine = vec![];
+                    }
+                    _ => self.world.do_action(&action),
+                }
+            } else if let Some(ref mut inv) = self.at_inventory {
+                let recipes = crafting::recipes();
+                match action {
+                    controls::Action::Move(MoveDir::Up) if inv.selected_recipe > 0 => {
+                        inv.selected_recipe -= 1;
+                        inv.amount = 1;
+                        inv.message = None;
+                    }
+                    controls::Action::Move(MoveDir::Down) if inv.selected_recipe + 1 < recipes.len() => {
+                        inv.selected_recipe += 1;
+                        inv.amount = 1;
+                        inv.message = None;
+                    }
+                    controls::Action::Move(MoveDir::Left) if inv.amount > 1 => {
+                        inv.amount -= 1;
+                    }
+                    controls::Action::Move(MoveDir::Right) if inv.amount < crafting::MAX_CRAFTS => {
+                        inv.amount += 1;
+                    }
+                    controls::Action::Select | controls::Action::CraftMax => {
+                        if let (Some(recipe), Some(id)) = (recipes.get(inv.selected_recipe), self.world.get_player_id()) {
+                            let amount =
+                                if let controls::Action::CraftMax = action {
+                                    match self.world.entities.get(&id) {
+                                        Some(entity::EntityWrapper::WPlayer(player)) => recipe.max_crafts(&player.inventory).max(1),
+                                        _ => 1,
+                                    }
+                                } else {
+                                    inv.amount
+                                };
+                            inv.message = Some(craft(&mut self.world, id, recipe, amount));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        self.keys_down.insert(key);
+    }
+
+    fn key_up(&mut self, key: key::Key) {
+        if let Some(ref mut rec) = self.recording {
+            rec.record(self.frame, KeyEvent::Up, key.clone());
+        }
+
+        self.keys_down.remove(&key);
+    }
+
+    /// Feed the keys from the replay that happened before this frame
+    fn play_replay(&mut self) {
+        let events = match self.replay {
+            Some(ref mut replay) => replay.events_for(self.frame),
+            None => return,
+        };
+        for (event, key) in events {
+            match event {
+                KeyEvent::Down => self.key_down(key),
+                KeyEvent::Up => self.key_up(key),
+            }
+        }
+    }
 }
 
-#[derive(Default, Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 struct AtInventory {
     selected_recipe: usize,
     scroll: u16,
+    // How many to craft at once
+    amount: u64,
+    // What happened with the last craft
+    message: Option<(String, bool)>,
+}
+
+impl AtInventory {
+    fn new() -> AtInventory {
+        AtInventory {
+            selected_recipe: 0,
+            scroll: 0,
+            amount: 1,
+            message: None,
+        }
+    }
+}
+
+/// Craft a recipe up to amount times, returning what to tell the player and if it worked
+fn craft(world: &mut World, player_id: u64, recipe: &crafting::Recipe, amount: u64) -> (String, bool) {
+    let mut crafted = 0;
+    while crafted < amount {
+        match entity::Player::craft(world, player_id, recipe) {
+            Ok(()) => crafted += 1,
+            Err(missing) => {
+                let missing = missing.iter().map(|x| format!("{}", x)).collect::<Vec<_>>().join(", ");
+                if crafted == 0 {
+                    return (format!("Need {}", missing), false);
+                }
+                return (format!("Crafted {}, then needed {}", crafted, missing), true);
+            }
+        }
+    }
+    (format!("Crafted {}", crafted), true)
 }
 
 lazy_static! {
     static ref GAME: Mutex<Game> = Mutex::new(
         Game {
-            state: GameState::Menu(Difficulty::Easy),
+            state: GameState::Menu(MenuState::new(Difficulty::EASY)),
             size: (0, 0)
         });
 }
 
+lazy_static! {
+    /// Bytes being passed between us and the page. The page makes room with `alloc_buffer` and
+    /// writes into it before calling something that reads it, and functions that hand bytes back
+    /// fill it and return how many they wrote.
+    static ref BUFFER: Mutex<Vec<u8>> = Mutex::new(vec![]);
+
+    /// The last saved run, if there is one
+    static ref SAVED_GAME: Mutex<Option<Vec<u8>>> = Mutex::new(None);
+
+    /// The recording of the last finished run
+    static ref LAST_RECORDING: Mutex<Option<Recording>> = Mutex::new(None);
+
+    /// The stats of the last finished run
+    static ref LAST_STATS: Mutex<Option<Stats>> = Mutex::new(None);
+}
+
+#[no_mangle]
+pub fn alloc_buffer(len: usize) -> *mut u8 {
+    let mut buffer = BUFFER.lock().unwrap();
+    *buffer = vec![0; len];
+    buffer.as_mut_ptr()
+}
+
+#[no_mangle]
+pub fn buffer_ptr() -> *const u8 {
+    BUFFER.lock().unwrap().as_ptr()
+}
+
+fn fill_buffer(data: &[u8]) -> usize {
+    let mut buffer = BUFFER.lock().unwrap();
+    *buffer = data.to_vec();
+    buffer.len()
+}
+
+/// Save the running game and put it in the buffer. Returns the length of the save, or 0 if the
+/// last run is over and the stored save should be forgotten. Outside of a run this returns the
+/// last save again.
+#[no_mangle]
+pub fn save_game() -> usize {
+    if let Ok(game) = GAME.try_lock() {
+        let mut saved = SAVED_GAME.lock().unwrap();
+        match game.state {
+            GameState::Playing(ref rouge) | GameState::Paused(ref rouge, _) => {
+                *saved = Some(save::save_world(&rouge.world));
+            }
+            GameState::GameOver(..) => {
+                *saved = None;
+            }
+            GameState::Menu(_) | GameState::Bindings(..) | GameState::Editor(..) => {}
+        }
+        saved.as_ref().map(|data| fill_buffer(data)).unwrap_or(0)
+    } else {
+        0
+    }
+}
+
+/// Hand us a save from the buffer that the player can continue from the menu
+#[no_mangle]
+pub fn load_saved_game(len: usize) {
+    let buffer = BUFFER.lock().unwrap();
+    if len <= buffer.len() {
+        *SAVED_GAME.lock().unwrap() = Some(buffer[..len].to_vec());
+    }
+}
+
+fn has_saved_game() -> bool {
+    SAVED_GAME.lock().map(|saved| saved.is_some()).unwrap_or(false)
+}
+
+fn continue_saved_game() {
+    let saved = match SAVED_GAME.lock().unwrap().clone() {
+        Some(saved) => saved,
+        None => return,
+    };
+
+    if let Ok(mut game) = GAME.try_lock() {
+        let (send, recv) = channel::<MetaAction>();
+
+        match save::load_world(&saved, send) {
+            Ok(world) => {
+                ext::clear();
+                game.state = GameState::Playing(WorldWrapper::new(world, recv));
+            }
+            Err(e) => {
+                ext::log(&format!("Can't load save: {}", e));
+                *SAVED_GAME.lock().unwrap() = None;
+            }
+        }
+    }
+}
+
+/// Put the recording of the current run, or the last one if we're not playing, in the buffer.
+/// Returns its length, or 0 if there is nothing to replay.
+#[no_mangle]
+pub fn export_recording() -> usize {
+    if let Ok(game) = GAME.try_lock() {
+        let current = match game.state {
+            GameState::Playing(ref rouge) | GameState::Paused(ref rouge, _) => rouge.recording.clone(),
+            _ => None,
+        };
+        current.or_else(|| LAST_RECORDING.lock().unwrap().clone())
+            .map(|rec| fill_buffer(&rec.to_bytes()))
+            .unwrap_or(0)
+    } else {
+        0
+    }
+}
+
+/// Put the stats of the current run, or the last one if we're not playing, in the buffer as
+/// "key = value" lines. Returns their length, or 0 if no run has been played.
+#[no_mangle]
+pub fn run_stats() -> usize {
+    if let Ok(game) = GAME.try_lock() {
+        let current = match game.state {
+            GameState::Playing(ref rouge) | GameState::Paused(ref rouge, _) => Some(rouge.world.run_stats()),
+            _ => None,
+        };
+        current.or_else(|| LAST_STATS.lock().unwrap().clone())
+            .map(|stats| fill_buffer(stats.to_text().as_bytes()))
+            .unwrap_or(0)
+    } else {
+        0
+    }
+}
+
+/// Start playing back a recording from the buffer. Returns if the recording could be read.
+#[no_mangle]
+pub fn start_replay(len: usize) -> bool {
+    let recording = {
+        let buffer = BUFFER.lock().unwrap();
+        if len > buffer.len() {
+            return false;
+        }
+        Recording::from_bytes(&buffer[..len])
+    };
+
+    match recording {
+        Ok(recording) => {
+            init_game(recording.difficulty, recording.seed);
+            if let Ok(mut game) = GAME.try_lock() {
+                if let GameState::Playing(ref mut rouge) = game.state {
+                    rouge.bindings = recording.bindings.clone();
+                    rouge.recording = None;
+                    rouge.replay = Some(Replay::new(recording));
+                }
+            }
+            true
+        }
+        Err(e) => {
+            ext::log(&format!("Can't read recording: {}", e));
+            false
+        }
+    }
+}
+
+/// Put the key bindings in the buffer as text, for the page to keep. Returns its length.
+#[no_mangle]
+pub fn export_bindings() -> usize {
+    fill_buffer(controls::bindings_to_string(&controls::bindings()).as_bytes())
+}
+
+/// Use a difficulty code from the buffer as the custom difficulty, and pick it if we're in the
+/// menu. Returns if the code could be read.
+#[no_mangle]
+pub fn load_difficulty_code(len: usize) -> bool {
+    let difficulty = match read_text(len).map(|code| Difficulty::from_code(&code)) {
+        Some(Ok(difficulty)) => difficulty,
+        Some(Err(e)) => {
+            ext::log(&format!("Can't load difficulty code: {}", e));
+            return false;
+        }
+        None => return false,
+    };
+
+    if let Ok(mut game) = GAME.try_lock() {
+        match game.state {
+            GameState::Menu(ref mut menu) => menu.use_custom(difficulty),
+            _ => ext::store(CUSTOM_DIFFICULTY_KEY, &difficulty.code()),
+        }
+    }
+    true
+}
+
+/// Load key bindings made by export_bindings from the buffer, like load_blocks
+#[no_mangle]
+pub fn load_bindings(len: usize) -> bool {
+    load_defs(len, "key bindings", controls::load_bindings)
+}
+
+/// Load extra block definitions (see defs/blocks.toml) from the buffer. Returns if they could be
+/// loaded, errors are logged.
+#[no_mangle]
+pub fn load_blocks(len: usize) -> bool {
+    load_defs(len, "blocks", block::load_blocks)
+}
+
+/// Load extra recipes (see defs/recipes.toml) from the buffer, like load_blocks
+#[no_mangle]
+pub fn load_recipes(len: usize) -> bool {
+    load_defs(len, "recipes", crafting::load_recipes)
+}
+
+/// Load loot tables (see defs/loot.toml) from the buffer, like load_blocks
+#[no_mangle]
+pub fn load_loot(len: usize) -> bool {
+    load_defs(len, "loot tables", loot::load_loot)
+}
+
+fn load_defs(len: usize, what: &str, load: fn(&str) -> Result<usize, DefError>) -> bool {
+    let text = {
+        let buffer = BUFFER.lock().unwrap();
+        if len > buffer.len() {
+            return false;
+        }
+        String::from_utf8(buffer[..len].to_vec())
+    };
+
+    match text.map_err(|_| "Not UTF-8".to_string()).and_then(|text| load(&text).map_err(|e| e.to_string())) {
+        Ok(count) => {
+            ext::log(&format!("Loaded {} {}", count, what));
+            true
+        }
+        Err(e) => {
+            ext::log(&format!("Can't load {}: {}", what, e));
+            false
+        }
+    }
+}
+
+fn random_seed() -> u32 {
+    (ext::rand() * u32::max_value() as f64) as u32
+}
+
 
 #[no_mangle]
 pub fn start(width: u16, height: u16) {
     ext::log("Started");
-    // Set panic hook
+    // Set panic hook. Natively the default one already prints to stderr.
+    #[cfg(target_arch = "wasm32")]
     set_hook(Box::new(|info| {
         ext::log(&format!("FATAL ERROR:"));
         if let Some(payload) = info.payload().downcast_ref::<&str>() {
@@ -96,52 +742,131 @@ pub fn start(width: u16, height: u16) {
 
     if let Ok(mut game) = GAME.try_lock() {
         game.size = (width, height);
+        game.state = GameState::Menu(MenuState::new(Difficulty::EASY));
+    }
+}
+
+/// Run something against the world being played, if there is one. It may be paused.
+pub fn with_world<T>(f: impl FnOnce(&mut World) -> T) -> Option<T> {
+    if let Ok(mut game) = GAME.try_lock() {
+        match game.state {
+            GameState::Playing(ref mut rouge) | GameState::Paused(ref mut rouge, _) => return Some(f(&mut rouge.world)),
+            _ => {}
+        }
     }
+    None
+}
+
+// Go from playing to paused or back. Anything else is left alone.
+fn toggle_pause(game: &mut Game) {
+    // Never seen, the real state is put back right away
+    let empty = GameState::GameOver(GameOverState {
+        difficulty: Difficulty::EASY,
+        seed: 0,
+        msg: RestartMessage::Died,
+        stats: Stats::default(),
+        place: None,
+        scores: vec![],
+    });
+    game.state = match std::mem::replace(&mut game.state, empty) {
+        GameState::Playing(rouge) => GameState::Paused(rouge, PauseState::new()),
+        GameState::Paused(rouge, _) => GameState::Playing(rouge),
+        other => other,
+    };
 }
 
 // Called 60 times every second from JavaScript
 #[no_mangle]
 pub fn tick() {
     if let Ok(mut game) = GAME.try_lock() {
-        let mut diff = Difficulty::Easy;
+        let mut diff = Difficulty::EASY;
+        let mut seed = 0;
 
         let mut actions_to_process = vec![];
         let size = game.size;
         match game.state {
             GameState::Playing(ref mut rouge) => {
                 diff = rouge.world.difficulty;
-                if let Some(inv) = rouge.at_inventory {
+                seed = rouge.world.seed;
+                rouge.play_replay();
+                if let Some(inv) = rouge.at_inventory.clone() {
                     rouge.world.draw(size);
                     draw_inventory(inv, rouge, size);
                 } else {
                     rouge.world.tick();
                     rouge.world.update_scroll(size);
                     rouge.world.draw(size);
+                    if let Some((ref message, worked)) = rouge.message {
+                        let col = if worked { (100, 255, 100) } else { (255, 100, 100) };
+                        ext::put_text((size.0.saturating_sub(message.chars().count() as u16) / 2, 0), message, col, (0, 0, 0));
+                    }
+                }
+                if let Some(ref replay) = rouge.replay {
+                    let text = if replay.is_done() { "REPLAY DONE" } else { "REPLAY" };
+                    ext::put_text((size.0.saturating_sub(text.len() as u16), 0), text, (255, 255, 0), (0, 0, 0));
                 }
+                rouge.frame += 1;
 
                 while let Ok(action) = rouge.action_receiver.try_recv() {
                     actions_to_process.push(action);
                 }
             }
-            GameState::Menu(difficulty) => {
-                draw_menu(difficulty, size);
+            GameState::Menu(ref menu) => {
+                draw_menu(menu, size);
             }
-            GameState::GameOver(_, msg) => {
-                draw_game_over(msg, size);
+            GameState::Bindings(ref screen, _) => {
+                draw_bindings(screen, size);
+            }
+            GameState::Editor(ref screen, _) => {
+                draw_editor(screen, size);
+            }
+            // The world isn't ticked, it's only drawn under the overlay
+            GameState::Paused(ref rouge, ref pause) => {
+                rouge.world.draw(size);
+                match pause.controls {
+                    Some(ref screen) => draw_bindings(screen, size),
+                    None => draw_pause(pause, size),
+                }
+            }
+            GameState::GameOver(ref over) => {
+                draw_game_over(over, size);
             }
         }
         ext::flip();
 
-        for action in actions_to_process {
-            match action {
-                MetaAction::Die => {
-                    game.state = GameState::GameOver(diff, RestartMessage::Died);
-                }
-                MetaAction::Win => {
-                    game.state = GameState::GameOver(diff, RestartMessage::Won);
+        let mut stats = Stats::default();
+        // Replays don't count towards the high scores
+        let mut watching = false;
+        if !actions_to_process.is_empty() {
+            if let GameState::Playing(ref rouge) = game.state {
+                if rouge.recording.is_some() {
+                    *LAST_RECORDING.lock().unwrap() = rouge.recording.clone();
                 }
+                stats = rouge.world.run_stats();
+                *LAST_STATS.lock().unwrap() = Some(stats.clone());
+                watching = rouge.replay.is_some();
             }
         }
+        if let Some(action) = actions_to_process.into_iter().last() {
+            let msg = match action {
+                MetaAction::Die => RestartMessage::Died,
+                MetaAction::Win => RestartMessage::Won,
+            };
+            let place = if watching {
+                None
+            } else {
+                let score = highscores::Score { won: msg == RestartMessage::Won, ticks: stats.ticks, seed: seed };
+                highscores::add(diff, score)
+            };
+            game.state = GameState::GameOver(GameOverState {
+                difficulty: diff,
+                seed: seed,
+                msg: msg,
+                stats: stats,
+                place: place,
+                scores: highscores::scores(diff),
+            });
+        }
     }
 }
 
@@ -153,7 +878,7 @@ pub fn resize(width: u16, height: u16) {
     }
 }
 
-fn draw_menu(difficulty: Difficulty, size: (u16, u16)) {
+fn draw_menu(menu: &MenuState, size: (u16, u16)) {
     ext::clear();
 
     // Border
@@ -173,24 +898,53 @@ fn draw_menu(difficulty: Difficulty, size: (u16, u16)) {
     // Title
     ext::put_text(((size.0 - TITLE.chars().count() as u16) / 2, 0), TITLE, (255, 255, 0), (255, 0, 0));
 
-    ext::put_text((1, 3), &format!("Diffiulty: {}", difficulty.to_string()), (255, 255, 255), (0, 0, 0));
+    ext::put_text((1, 3), &format!("Diffiulty: {}", menu.difficulty.to_string()), (255, 255, 255), (0, 0, 0));
+    ext::put_text((1, 4), &format!("Seed: {}", menu.seed), (255, 255, 255), (0, 0, 0));
+    ext::put_text((1, 5), "Type a number to pick your own seed", (150, 150, 150), (0, 0, 0));
 
-    ext::put_text((1, 6), "Press enter to start!", (255, 255, 255), (0, 0, 0));
+    ext::put_text((1, 7), "Press enter to start!", (255, 255, 255), (0, 0, 0));
+    if has_saved_game() {
+        ext::put_text((1, 8), "Press C to continue your saved run", (255, 255, 255), (0, 0, 0));
+    }
+    ext::put_text((1, 9), "Press K to change the controls", (255, 255, 255), (0, 0, 0));
+    ext::put_text((1, 10), "Press D to make a custom difficulty", (255, 255, 255), (0, 0, 0));
+    ext::put_text((1, 11), "Press Esc during a run to pause it", (150, 150, 150), (0, 0, 0));
+
+    // The best runs on the difficulty picked now
+    let x = size.0 / 2 + 2;
+    ext::put_text((x, 3), &format!("Best on {}", menu.difficulty.to_string()), (255, 255, 0), (0, 0, 0));
+    if menu.scores.is_empty() {
+        ext::put_text((x, 4), "No runs yet", (150, 150, 150), (0, 0, 0));
+    }
+    for (i, score) in menu.scores.iter().take(MENU_SCORES).enumerate() {
+        ext::put_text((x, 4 + i as u16), &format!("{}. {}", i + 1, score.describe()), (255, 255, 255), (0, 0, 0));
+    }
 
 
-    // Controls
+    // Controls, as they are bound now
     let mut controls_actions = vec![];
+    let bindings = controls::bindings();
 
-    for cont in controls::CONTROLS.iter().rev() {
-        let mut keys =
-                cont.modifiers.iter()
-                .map(|x| format!("{}", x))
-                .collect::<Vec<_>>();
+    for cont in controls::DEFAULT_CONTROLS.iter().rev() {
+        let current = cont.keys.iter()
+            .filter_map(|(_, action)| bindings.iter().find(|(other, _)| other == action).map(|(_, binding)| binding))
+            .collect::<Vec<_>>();
 
-        let mut ending = cont.keys.keys().map(|x| format!("{}", x)).collect::<Vec<_>>();
-        ending.sort();
+        let mut keys;
+        if current.iter().all(|binding| binding.modifiers == current[0].modifiers) {
+            keys =
+                    current[0].modifiers.iter()
+                    .map(|x| format!("{}", x))
+                    .collect::<Vec<_>>();
 
-        keys.push(ending.into_iter().collect());
+            let mut ending = current.iter().map(|binding| format!("{}", binding.key)).collect::<Vec<_>>();
+            ending.sort();
+
+            keys.push(ending.into_iter().collect());
+        } else {
+            // Some were rebound on their own
+            keys = vec![current.iter().map(|binding| binding.to_string()).collect::<Vec<_>>().join(" ")];
+        }
 
         controls_actions.push(
             (keys,
@@ -220,19 +974,152 @@ fn draw_menu(difficulty: Difficulty, size: (u16, u16)) {
 
 }
 
-fn draw_game_over(msg: RestartMessage, _size: (u16, u16)) {
+fn draw_bindings(screen: &BindingsState, size: (u16, u16)) {
+    ext::clear();
+
+    ext::put_text(((size.0 - BINDINGS_TITLE.chars().count() as u16) / 2, 0), BINDINGS_TITLE, (255, 255, 0), (255, 0, 0));
+    ext::put_text(
+        (1, 2),
+        "Up/Down: pick an action, Enter: change its keys, Backspace: reset them, K/Esc: back",
+        (150, 150, 150), (0, 0, 0));
+
+    // Keep the selected action on screen
+    let visible = size.1.saturating_sub(7).max(1) as usize;
+    let first = if screen.selected < visible { 0 } else { screen.selected + 1 - visible };
+
+    for (i, (action, binding)) in controls::bindings().iter().enumerate().skip(first).take(visible) {
+        let y = 4 + (i - first) as u16;
+        let selected = i == screen.selected;
+        if selected {
+            ext::put_char((1, y), &Shape::new('>', (255, 255, 0), (0, 0, 0)));
+        }
+        ext::put_text((3, y), &action.desc(), if selected { (255, 255, 0) } else { (255, 255, 255) }, (0, 0, 0));
+
+        let keys = match screen.new_keys {
+            Some(_) if selected => "Press the new keys, or Backspace to cancel".to_string(),
+            _ => binding.to_string(),
+        };
+        ext::put_text((20, y), &keys, (255, 255, 200), (0, 0, 0));
+    }
+
+    if let Some((ref message, worked)) = screen.message {
+        let col = if worked { (100, 255, 100) } else { (255, 100, 100) };
+        ext::put_text((1, size.1 - 2), message, col, (0, 0, 0));
+    }
+}
+
+fn draw_editor(screen: &EditorState, size: (u16, u16)) {
+    ext::clear();
+
+    ext::put_text(((size.0 - EDITOR_TITLE.chars().count() as u16) / 2, 0), EDITOR_TITLE, (255, 255, 0), (255, 0, 0));
+    ext::put_text(
+        (1, 2),
+        "Up/Down: pick a value, Left/Right: change it, Enter/D/Esc: back",
+        (150, 150, 150), (0, 0, 0));
+
+    for (i, &param) in PARAMS.iter().enumerate() {
+        let y = 4 + i as u16;
+        let selected = i == screen.selected;
+        if selected {
+            ext::put_char((1, y), &Shape::new('>', (255, 255, 0), (0, 0, 0)));
+        }
+        ext::put_text((3, y), param.desc(), if selected { (255, 255, 0) } else { (255, 255, 255) }, (0, 0, 0));
+        ext::put_text((36, y), &screen.difficulty.get(param).to_string(), (255, 255, 200), (0, 0, 0));
+        let (min, max) = param.range();
+        ext::put_text((42, y), &format!("({} to {})", min, max), (150, 150, 150), (0, 0, 0));
+    }
+
+    let y = 5 + PARAMS.len() as u16;
+    match screen.code {
+        Some(ref code) => {
+            ext::put_text((1, y), &format!("Code: {}_", code), (255, 255, 0), (0, 0, 0));
+            ext::put_text((1, y + 1), "Enter: use this code, Esc: cancel", (150, 150, 150), (0, 0, 0));
+        }
+        None => {
+            ext::put_text((1, y), &format!("Code: {}", screen.difficulty.code()), (255, 255, 255), (0, 0, 0));
+            ext::put_text((1, y + 1), "Share the code, or type one in with numbers and dashes", (150, 150, 150), (0, 0, 0));
+        }
+    }
+
+    if let Some((ref message, worked)) = screen.message {
+        let col = if worked { (100, 255, 100) } else { (255, 100, 100) };
+        ext::put_text((1, size.1 - 2), message, col, (0, 0, 0));
+    }
+}
+
+fn draw_pause(pause: &PauseState, size: (u16, u16)) {
+    let width = 40;
+    let height = PAUSE_OPTIONS.len() as u16 + 6;
+    let left = size.0.saturating_sub(width) / 2;
+    let top = size.1.saturating_sub(height) / 2;
+
+    for x in left..left + width {
+        for y in top..top + height {
+            let edge = if y == top || y == top + height - 1 { '=' } else if x == left || x == left + width - 1 { '|' } else { ' ' };
+            ext::put_char((x, y), &Shape::new(edge, (255, 255, 255), (0, 0, 0)));
+        }
+    }
+    ext::put_text((left + (width - PAUSE_TITLE.chars().count() as u16) / 2, top), PAUSE_TITLE, (255, 255, 0), (255, 0, 0));
+
+    for (i, option) in PAUSE_OPTIONS.iter().enumerate() {
+        let y = top + 2 + i as u16;
+        let selected = i == pause.selected;
+        if selected {
+            ext::put_char((left + 2, y), &Shape::new('>', (255, 255, 0), (0, 0, 0)));
+        }
+        ext::put_text((left + 4, y), option, if selected { (255, 255, 0) } else { (255, 255, 255) }, (0, 0, 0));
+    }
+
+    if let Some((ref message, worked)) = pause.message {
+        let col = if worked { (100, 255, 100) } else { (255, 100, 100) };
+        ext::put_text((left + 2, top + height - 2), message, col, (0, 0, 0));
+    }
+}
+
+fn draw_game_over(over: &GameOverState, _size: (u16, u16)) {
     ext::clear();
+    let stats = &over.stats;
 
     ext::put_text((0, 3), "game over lol. press enter to continue", (255, 255, 255), (0, 0, 0));
 
-    let (text, col) = match msg {
+    let (text, col) = match over.msg {
         RestartMessage::Died => (&"u ded lol!", (255, 0, 0)),
         RestartMessage::Won  => (&"gj u won", (0, 255, 0)),
     };
 
     ext::put_text((0, 0), text, col, (0, 0, 0));
+    ext::put_text((0, 1), &format!("seed {} on {}", over.seed, over.difficulty.to_string()), (150, 150, 150), (0, 0, 0));
+
+    let mut lines = vec![
+        format!("Survived {} ({} ticks)", stats.time(), stats.ticks),
+        format!("Police killed: {}", stats.police_killed),
+        format!("Blocks mined: {}", stats.blocks_mined),
+        format!("Blocks placed: {}", stats.blocks_placed),
+        format!("Items crafted: {}", stats.items_crafted),
+        format!("Damage taken: {}", stats.damage_taken()),
+    ];
+    for (source, amount) in &stats.damage {
+        lines.push(format!("  {} from {}", amount, source));
+    }
+    if let Some(health) = stats.josef_health {
+        lines.push(format!("Josef's health left: {}", health));
+    }
+    for (i, line) in lines.iter().enumerate() {
+        ext::put_text((0, 5 + i as u16), line, (255, 255, 255), (0, 0, 0));
+    }
 
-
+    // The high scores, with this run in yellow if it made it
+    let top = 6 + lines.len() as u16;
+    let title = match over.place {
+        Some(0) => "New best run!".to_string(),
+        Some(place) => format!("Number {} on the high scores", place + 1),
+        None => "Best runs".to_string(),
+    };
+    ext::put_text((0, top), &title, (255, 255, 0), (0, 0, 0));
+    for (i, score) in over.scores.iter().enumerate() {
+        let col = if Some(i) == over.place { (255, 255, 0) } else { (255, 255, 255) };
+        ext::put_text((0, top + 1 + i as u16), &format!("{}. {}", i + 1, score.describe()), col, (0, 0, 0));
+    }
 }
 
 fn draw_inventory(inv: AtInventory, ww: &mut WorldWrapper, size: (u16, u16)) {
@@ -292,7 +1179,7 @@ fn draw_inventory(inv: AtInventory, ww: &mut WorldWrapper, size: (u16, u16)) {
     // Draw inventory
     if let Some(entity::EntityWrapper::WPlayer(ref player)) =
         ww.world.get_player_id().and_then(|x| ww.world.entities.get(&x)) {
-        for (i, (item, count)) in player.inventory.iter().enumerate() {
+        for (i, (item, count)) in player.inventory.slots().iter().enumerate() {
             ext::put_char(
                 (INVENTORY_INDENT + 2, INVENTORY_INDENT + i as u16 + 2),
                 &item.get_shape());
@@ -301,14 +1188,20 @@ fn draw_inventory(inv: AtInventory, ww: &mut WorldWrapper, size: (u16, u16)) {
                 &format!("x{} - {}", count, item.get_name()),
                 (255, 255, 255), (0, 0, 0));
         }
+        let used = player.inventory.slots().len();
+        ext::put_text(
+            (INVENTORY_INDENT + 2, INVENTORY_INDENT + used as u16 + 3),
+            &format!("{}/{} slots used", used, player.inventory.capacity()),
+            (150, 150, 150), (0, 0, 0));
     }
 
     // Helpers to keep in bounds. Returns:
     //     None - The text was placed
     //     Some(false) - The text is to far up to be placed
     //     Some(true) - The text is to far down to be placed
+    let scroll = inv.scroll;
     let draw_crafting_str = move |pos: (u16, u16), text: &str, fg: (u8, u8, u8), bg: (u8, u8, u8)| {
-        let pos_ = (pos.0 + size.0 / 2, INVENTORY_INDENT + pos.1 - inv.scroll);
+        let pos_ = (pos.0 + size.0 / 2, INVENTORY_INDENT + pos.1 - scroll);
         if pos_.1 <= INVENTORY_INDENT + 1 {
             return Some(false);
         }
@@ -320,7 +1213,7 @@ fn draw_inventory(inv: AtInventory, ww: &mut WorldWrapper, size: (u16, u16)) {
     };
 
     let draw_crafting_shape = move |pos: (u16, u16), sh: &Shape| {
-        let pos_ = (pos.0 + size.0 / 2, INVENTORY_INDENT + pos.1 - inv.scroll);
+        let pos_ = (pos.0 + size.0 / 2, INVENTORY_INDENT + pos.1 - scroll);
         if pos_.1 <= INVENTORY_INDENT + 1 {
             return Some(false);
         }
@@ -337,38 +1230,79 @@ fn draw_inventory(inv: AtInventory, ww: &mut WorldWrapper, size: (u16, u16)) {
     let mut scroll_move: i16 = 0;
 
     let mut y = 2;
-    // Draw recipes
-    for (i, recipe) in crafting::RECIPES.iter().enumerate() {
+    // Draw recipes, highlighted if they can be crafted here
+    let player_id = ww.world.get_player_id();
+    let inventory = match player_id.and_then(|id| ww.world.entities.get(&id)) {
+        Some(entity::EntityWrapper::WPlayer(player)) => player.inventory.clone(),
+        _ => Inventory::new(0),
+    };
+    let have_color = |have: bool| if have { CRAFT_HAVE_COLOR } else { CRAFT_MISSING_COLOR };
+
+    for (i, recipe) in crafting::recipes().iter().enumerate() {
+        let missing = player_id.map(|id| recipe.missing(&ww.world, id)).unwrap_or(vec![]);
+        let craftable = player_id.is_some() && missing.is_empty();
         let mut drawn: Option<bool> = None;
         drawn = drawn.or(
-            draw_crafting_shape((1, y), &recipe.out.get_shape())
+            draw_crafting_shape((1, y), &recipe.out[0].0.get_shape())
             );
         if i == inv.selected_recipe {
             drawn = drawn.or(
                 draw_crafting_str(
                     (4, y),
-                    &recipe.out.get_name(),
-                    (255, 255, 255),
+                    &recipe.get_name(),
+                    if craftable { (170, 255, 170) } else { (200, 200, 200) },
                     (0, 0, 0))
                 );
-            let desc = recipe.out.get_desc();
+            let desc = recipe.out.iter().map(|(item, _)| item.get_desc()).collect::<Vec<_>>().join(" ");
 
             let lines = wrapper.wrap_iter(&desc);
             for line in lines {
                 y += 1;
                 drawn = drawn.or(draw_crafting_str((2, y as u16), &*line, (150, 150, 255), (0, 0, 0)));
             }
+
+            y += 1;
+            drawn = drawn.or(
+                draw_crafting_str(
+                    (2, y),
+                    &format!("Craft < {} >  (enough for {})", inv.amount, recipe.max_crafts(&inventory)),
+                    (255, 255, 255),
+                    (0, 0, 0)));
+
             for (needed, amount) in recipe.needed.iter() {
                 y += 1;
+                let have = inventory.count(needed);
+                let need = amount * inv.amount;
                 drawn = drawn.or(draw_crafting_shape((3, y), &needed.get_shape()));
                 drawn = drawn.or(
                     draw_crafting_str(
-                              (4, y),
-                              &format!("x{}", amount),
-                              (255, 255, 255),
+                              (5, y),
+                              &format!("{}/{} {}", have, need, needed.get_name()),
+                              have_color(have >= need),
                               (0, 0, 0)
                           ));
             }
+            for tool in recipe.tools.iter() {
+                y += 1;
+                drawn = drawn.or(draw_crafting_shape((3, y), &tool.get_shape()));
+                drawn = drawn.or(
+                    draw_crafting_str(
+                        (5, y),
+                        &format!("{} (tool, kept)", tool.get_id().replace('_', " ")),
+                        have_color(!missing.contains(&crafting::Missing::Tool(tool.clone()))),
+                        (0, 0, 0)));
+            }
+            if let Some(ref station) = recipe.station {
+                y += 1;
+                let at_station = !missing.contains(&crafting::Missing::Station(station.clone()));
+                drawn = drawn.or(draw_crafting_str((2, y), "Next to", have_color(at_station), (0, 0, 0)));
+                drawn = drawn.or(draw_crafting_shape((10, y), &station.get_shape()));
+                drawn = drawn.or(draw_crafting_str((12, y), &station.name, have_color(at_station), (0, 0, 0)));
+            }
+            if let Some((ref message, worked)) = inv.message {
+                y += 1;
+                drawn = drawn.or(draw_crafting_str((2, y), message, have_color(worked), (0, 0, 0)));
+            }
             match drawn {
                 Some(false) => scroll_move = -1,
                 Some(true)  => scroll_move = 1,
@@ -377,8 +1311,8 @@ fn draw_inventory(inv: AtInventory, ww: &mut WorldWrapper, size: (u16, u16)) {
         } else {
             draw_crafting_str(
                 (4, y),
-                &recipe.out.get_name(),
-                (120, 120, 120),
+                &recipe.get_name(),
+                if craftable { (120, 220, 120) } else { (70, 70, 70) },
                 (0, 0, 0));
         }
         y += 3;
@@ -392,16 +1326,12 @@ fn draw_inventory(inv: AtInventory, ww: &mut WorldWrapper, size: (u16, u16)) {
     }
 }
 
-pub fn init_game(difficulty: Difficulty) {
+pub fn init_game(difficulty: Difficulty, seed: u32) {
     if let Ok(mut game) = GAME.try_lock() {
         let (send, recv) = channel::<MetaAction>();
 
-        let mut rouge = WorldWrapper {
-            world: World::empty(difficulty, send),
-            action_receiver: recv,
-            keys_down: HashSet::new(),
-            at_inventory: None,
-        };
+        let mut rouge = WorldWrapper::new(World::empty(difficulty, seed, send), recv);
+        rouge.recording = Some(Recording::new(difficulty, seed, rouge.bindings.clone()));
 
         rouge.world.generate(WORLD_SIZE.0, WORLD_SIZE.1);;
 
@@ -411,51 +1341,13 @@ pub fn init_game(difficulty: Difficulty) {
     }
 }
 
+/// A key was pressed, by its KeyboardEvent.keyCode
 #[no_mangle]
 pub fn key_down(key_code: u8) {
     match key::parse_key(key_code) {
         Some(key) => {
             ext::log(&format!("Pressed key: {} -> {:?}", key_code, key));
-            if let Ok(mut game) = GAME.try_lock() {
-                match game.state {
-                    GameState::Playing(ref mut rouge) => {
-                        if let Some(ref action) = controls::parse_control(&key, &rouge.keys_down) {
-                            ext::log(&format!("Action: {:?}", action));
-                            if let controls::Action::ToggleInventory = action {
-                                if rouge.at_inventory.is_some() {
-                                    rouge.at_inventory = None;
-                                } else {
-                                    rouge.at_inventory = Some(AtInventory::default());
-                                }
-                            }
-                            if rouge.at_inventory.is_none() {
-                                rouge.world.do_action(&action);
-                            } else if let Some(ref mut inv) = rouge.at_inventory {
-                                match action {
-                                    controls::Action::Move(MoveDir::Up) if inv.selected_recipe > 0 => {
-                                        inv.selected_recipe -= 1;
-                                    }
-                                    controls::Action::Move(MoveDir::Down) if inv.selected_recipe < crafting::RECIPES.len() - 1 => {
-                                        inv.selected_recipe += 1;
-                                    }
-                                    controls::Action::Select => {
-                                        let curr_recipe = &crafting::RECIPES[inv.selected_recipe];
-                                        if let Some(entity::EntityWrapper::WPlayer(player)) =
-                                            rouge.world.get_player_id().and_then(|x| rouge.world.entities.get_mut(&x)) {
-                                            player.craft(curr_recipe);
-                                        }
-                                    }
-                                    _ => {}
-                                }
-                            }
-                        }
-                        rouge.keys_down.insert(key);
-                    }
-                    _ => {
-
-                    }
-                }
-            }
+            press(key);
         }
         None => {
             ext::log(&format!("Pressed key: {}", key_code));
@@ -465,30 +1357,193 @@ pub fn key_down(key_code: u8) {
 
 #[no_mangle]
 pub fn key_up(key_code: u8) {
-    let mut start: Option<Difficulty> = None;
+    if let Some(key) = key::parse_key(key_code) {
+        release(key);
+    }
+}
+
+/// A key was pressed, by its KeyboardEvent.code in the buffer. Codes don't depend on the keyboard
+/// layout, so this is better than key_down when the page has them.
+#[no_mangle]
+pub fn key_down_code(len: usize) {
+    let code = match read_text(len) {
+        Some(code) => code,
+        None => return,
+    };
+    match key::parse_code(&code) {
+        Some(key) => {
+            ext::log(&format!("Pressed key: {} -> {:?}", code, key));
+            press(key);
+        }
+        None => {
+            ext::log(&format!("Pressed key: {}", code));
+        }
+    }
+}
+
+/// Like key_down_code, but for letting go
+#[no_mangle]
+pub fn key_up_code(len: usize) {
+    if let Some(key) = read_text(len).and_then(|code| key::parse_code(&code)) {
+        release(key);
+    }
+}
+
+fn read_text(len: usize) -> Option<String> {
+    let buffer = BUFFER.lock().unwrap();
+    if len > buffer.len() {
+        return None;
+    }
+    String::from_utf8(buffer[..len].to_vec()).ok()
+}
+
+fn press(key: key::Key) {
+    if let Ok(mut game) = GAME.try_lock() {
+        match game.state {
+            // Replays only listen to the recording
+            // Escape pauses when it's let go
+            GameState::Playing(ref mut rouge) if rouge.replay.is_none() && key != key::Key::Escape => {
+                rouge.key_down(key);
+            }
+            GameState::Bindings(ref mut screen, _) => {
+                screen.key_down(key);
+            }
+            GameState::Paused(_, PauseState { controls: Some(ref mut screen), .. }) => {
+                screen.key_down(key);
+            }
+            _ => {
+
+            }
+        }
+    }
+}
+
+fn release(key: key::Key) {
+    let mut start: Option<(Difficulty, u32)> = None;
     let mut next_state: Option<GameState> = None;
+    let mut continue_game = false;
+    let mut pause = false;
 
-    if let Some(key) = key::parse_key(key_code) {
-        if let Ok(mut game) = GAME.try_lock() {
-            match game.state {
-                GameState::Playing(ref mut rouge) => {
-                    rouge.keys_down.remove(&key);
+    if let Ok(mut game) = GAME.try_lock() {
+        match game.state {
+            GameState::Playing(ref mut rouge) => {
+                if rouge.replay.is_none() && key == key::Key::Escape {
+                    pause = true;
+                } else if rouge.replay.is_none() {
+                    rouge.key_up(key);
+                } else if key == key::Key::Enter {
+                    // Stop watching the replay
+                    next_state = Some(GameState::Menu(MenuState::new(rouge.world.difficulty)));
                 }
-                GameState::Menu(ref mut difficulty) => {
-                    match key {
-                        key::Key::Arrow(MoveDir::Right) => { *difficulty = difficulty.harder() }
-                        key::Key::Arrow(MoveDir::Left)  => { *difficulty = difficulty.easier() }
-                        key::Key::Enter => { start = Some(*difficulty); }
-                        _ => {}
+            }
+            GameState::Menu(ref mut menu) => {
+                match key {
+                    // The custom difficulty comes after the hardest preset
+                    key::Key::Arrow(MoveDir::Right) => {
+                        let difficulty = match menu.difficulty.preset {
+                            Preset::Reality => menu.custom,
+                            _ => menu.difficulty.harder(),
+                        };
+                        menu.set_difficulty(difficulty);
                     }
+                    key::Key::Arrow(MoveDir::Left) => {
+                        let difficulty = match menu.difficulty.preset {
+                            Preset::Custom => Difficulty::REALITY,
+                            _ => menu.difficulty.easier(),
+                        };
+                        menu.set_difficulty(difficulty);
+                    }
+                    key::Key::Digit(d) | key::Key::Numpad(d) => {
+                        if !menu.typed_seed {
+                            menu.seed = 0;
+                            menu.typed_seed = true;
+                        }
+                        menu.seed = menu.seed.checked_mul(10)
+                            .and_then(|s| s.checked_add(d as u32))
+                            .unwrap_or(menu.seed);
+                    }
+                    key::Key::Backspace => {
+                        menu.seed /= 10;
+                        menu.typed_seed = true;
+                    }
+                    key::Key::Enter => { start = Some((menu.difficulty, menu.seed)); }
+                    _ if key == key::Key::letter('C') => { continue_game = true; }
+                    _ if key == key::Key::letter('K') => { next_state = Some(GameState::Bindings(BindingsState::new(), menu.clone())); }
+                    // Not a letter used as a modifier, those are held until the next key in the terminal
+                    _ if key == key::Key::letter('D') => { next_state = Some(GameState::Editor(EditorState::new(menu.difficulty), menu.clone())); }
+                    _ => {}
+                }
+            }
+            GameState::Bindings(ref mut screen, ref menu) => {
+                if screen.key_up(key) {
+                    next_state = Some(GameState::Menu(menu.clone()));
                 }
-                GameState::GameOver(difficulty, _) => {
+            }
+            GameState::Editor(ref mut screen, ref mut menu) => {
+                if screen.key_up(key) {
+                    menu.use_custom(screen.difficulty);
+                    next_state = Some(GameState::Menu(menu.clone()));
+                }
+            }
+            GameState::Paused(ref mut rouge, ref mut paused) => {
+                if rouge.keys_down.contains(&key) {
+                    // Let go of a key held from before pausing
+                    rouge.key_up(key);
+                } else if let Some(ref mut screen) = paused.controls {
+                    if screen.key_up(key) {
+                        paused.controls = None;
+                        let bindings = controls::bindings();
+                        if bindings != rouge.bindings {
+                            rouge.bindings = bindings;
+                            // The recording would be played back with the old bindings
+                            rouge.recording = None;
+                        }
+                    }
+                } else {
                     match key {
-                        key::Key::Enter => { next_state = Some(GameState::Menu(difficulty)); }
+                        key::Key::Escape => { pause = true; }
+                        key::Key::Arrow(MoveDir::Up) if paused.selected > 0 => {
+                            paused.selected -= 1;
+                            paused.confirm_restart = false;
+                            paused.message = None;
+                        }
+                        key::Key::Arrow(MoveDir::Down) if paused.selected + 1 < PAUSE_OPTIONS.len() => {
+                            paused.selected += 1;
+                            paused.confirm_restart = false;
+                            paused.message = None;
+                        }
+                        key::Key::Enter => match paused.selected {
+                            0 => { pause = true; }
+                            1 if paused.confirm_restart => { start = Some((rouge.world.difficulty, rouge.world.seed)); }
+                            1 => {
+                                paused.confirm_restart = true;
+                                paused.message = Some(("Press Enter again to restart".into(), false));
+                            }
+                            2 => {
+                                *SAVED_GAME.lock().unwrap() = Some(save::save_world(&rouge.world));
+                                paused.message = Some(("Saved, press C in the menu to continue".into(), true));
+                            }
+                            3 => { paused.controls = Some(BindingsState::new()); }
+                            _ => {
+                                if rouge.recording.is_some() {
+                                    *LAST_RECORDING.lock().unwrap() = rouge.recording.clone();
+                                }
+                                next_state = Some(GameState::Menu(MenuState::new(rouge.world.difficulty)));
+                            }
+                        },
                         _ => {}
                     }
                 }
             }
+            GameState::GameOver(ref over) => {
+                match key {
+                    key::Key::Enter => { next_state = Some(GameState::Menu(MenuState::new(over.difficulty))); }
+                    _ => {}
+                }
+            }
+        }
+        if pause {
+            toggle_pause(&mut game);
         }
     }
     if let Some(next_state) = next_state {
@@ -496,8 +1551,11 @@ pub fn key_up(key_code: u8) {
             game.state = next_state;
         }
     }
-    if let Some(difficulty) = start {
-        init_game(difficulty);
+    if let Some((difficulty, seed)) = start {
+        init_game(difficulty, seed);
+    }
+    if continue_game {
+        continue_saved_game();
     }
 }
 
@@ -506,7 +1564,7 @@ pub fn redraw() {
     if let Ok(game) = GAME.try_lock() {
         ext::clear();
         match game.state {
-            GameState::Playing(ref rouge) => {
+            GameState::Playing(ref rouge) | GameState::Paused(ref rouge, _) => {
                 rouge.world.draw(game.size);
             }
             _ => { }
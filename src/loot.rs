@@ -0,0 +1,109 @@
+// What enemies drop when they die, see defs/loot.toml
+
+use inventory::InventoryItem;
+use entity::{EntityWrapper, ItemDrop};
+use world::World;
+use defs::{self, DefError, Section, Value};
+
+use std::sync::Mutex;
+
+const DEFAULT_LOOT: &str = include_str!("../defs/loot.toml");
+
+// The enemies that can have loot tables
+const ENEMIES: &[&str] = &["police"];
+
+lazy_static! {
+    static ref TABLES: Mutex<Vec<LootTable>> = Mutex::new(
+        parse_tables(DEFAULT_LOOT)
+            .unwrap_or_else(|e| panic!("Broken defs/loot.toml: {}", e))
+        );
+}
+
+#[derive(Clone)]
+pub struct LootTable {
+    pub enemy: String,
+    pub drops: Vec<LootDrop>,
+}
+
+#[derive(Clone)]
+pub struct LootDrop {
+    pub item: InventoryItem,
+    pub amount: u64,
+    // In percent
+    pub chance: u8,
+}
+
+pub fn table(enemy: &str) -> Option<LootTable> {
+    TABLES.lock().unwrap().iter()
+        .find(|table| table.enemy == enemy)
+        .cloned()
+}
+
+/// Load loot tables from a definition file, in the same format as defs/loot.toml. They replace
+/// the tables for the same enemies. If anything in the file is wrong, nothing is changed. Returns
+/// how many tables were loaded.
+pub fn load_loot(text: &str) -> Result<usize, DefError> {
+    let new = parse_tables(text)?;
+    let count = new.len();
+
+    let mut tables = TABLES.lock().unwrap();
+    for table in new {
+        match tables.iter().position(|old| old.enemy == table.enemy) {
+            Some(idx) => tables[idx] = table,
+            None => tables.push(table),
+        }
+    }
+    Ok(count)
+}
+
+/// Roll an enemy's loot table and put whatever it drops on the ground
+pub fn drop_loot(world: &mut World, enemy: &str, pos: (u16, u16)) {
+    let table = match table(enemy) {
+        Some(table) => table,
+        None => return,
+    };
+    for drop in table.drops {
+        if world.rng.rand() * 100. < drop.chance as f64 {
+            world.add_entity(EntityWrapper::WItemDrop(ItemDrop::new(pos, drop.item, drop.amount)));
+        }
+    }
+}
+
+fn parse_tables(text: &str) -> Result<Vec<LootTable>, DefError> {
+    defs::parse(text)?.iter().map(parse_table).collect()
+}
+
+fn parse_table(sec: &Section) -> Result<LootTable, DefError> {
+    sec.check_keys(&["drops"])?;
+    if !ENEMIES.contains(&sec.name.as_str()) {
+        return Err(DefError::new(sec.line, format!("Unknown enemy {}, it should be one of {}", sec.name, ENEMIES.join(", "))));
+    }
+
+    let line = sec.key_line("drops");
+    let mut drops = vec![];
+    for val in sec.require("drops", sec.list("drops"))? {
+        let drop = match val {
+            Value::List(ref drop) if drop.len() == 3 => match (&drop[0], &drop[1], &drop[2]) {
+                (Value::Str(id), Value::Int(amount), Value::Int(chance)) => {
+                    let item = InventoryItem::by_id(id)
+                        .ok_or_else(|| DefError::new(line, format!("Unknown item {}", id)))?;
+                    if *amount <= 0 {
+                        return Err(DefError::new(line, format!("[{}] drops {} {}, it should be at least 1", sec.name, amount, id)));
+                    }
+                    if *chance < 0 || *chance > 100 {
+                        return Err(DefError::new(line, format!("The chance of {} in [{}] should be 0-100", id, sec.name)));
+                    }
+                    LootDrop { item: item, amount: *amount as u64, chance: *chance as u8 }
+                }
+                _ => return Err(DefError::new(line, format!("drops in [{}] should be a list of [item, amount, chance]", sec.name))),
+            },
+            _ => return Err(DefError::new(line, format!("drops in [{}] should be a list of [item, amount, chance]", sec.name))),
+        };
+        drops.push(drop);
+    }
+
+    Ok(LootTable {
+        enemy: sec.name.clone(),
+        drops: drops,
+    })
+}
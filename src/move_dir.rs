@@ -1,4 +1,4 @@
-use ext::rand;
+use rng::Rng;
 
 pub const DIRECTIONS: [MoveDir; 4] = [ MoveDir::Up, MoveDir::Left, MoveDir::Down, MoveDir::Right ];
 
@@ -32,8 +32,8 @@ impl MoveDir {
     }
 }
 
-pub fn random_dir() -> MoveDir {
-    match (rand() * 4.) as usize {
+pub fn random_dir(rng: &mut Rng) -> MoveDir {
+    match (rng.rand() * 4.) as usize {
         0 => MoveDir::Left,
         1 => MoveDir::Right,
         2 => MoveDir::Up,
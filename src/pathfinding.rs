@@ -0,0 +1,195 @@
+// A* over the blocks of a world, used by everything that needs to get somewhere.
+
+use world::World;
+use block::Block;
+use move_dir::{MoveDir, DIRECTIONS};
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::u32;
+
+/// One bit for every position in a world
+pub struct Grid {
+    height: usize,
+    bits: Vec<u64>,
+}
+
+impl Grid {
+    pub fn new(width: usize, height: usize) -> Grid {
+        Grid {
+            height: height,
+            bits: vec![0; (width * height + 63) / 64],
+        }
+    }
+
+    fn index(&self, pos: (u16, u16)) -> usize {
+        pos.0 as usize * self.height + pos.1 as usize
+    }
+
+    pub fn contains(&self, pos: (u16, u16)) -> bool {
+        let idx = self.index(pos);
+        self.bits[idx / 64] & (1 << (idx % 64)) != 0
+    }
+
+    /// Returns false if the position was already set
+    pub fn insert(&mut self, pos: (u16, u16)) -> bool {
+        let idx = self.index(pos);
+        let was_set = self.bits[idx / 64] & (1 << (idx % 64)) != 0;
+        self.bits[idx / 64] |= 1 << (idx % 64);
+        !was_set
+    }
+}
+
+#[derive(PartialEq, Eq)]
+struct Node {
+    // cost so far + heuristic
+    estimate: u32,
+    heuristic: u32,
+    // Nodes pushed earlier win ties, so searches don't depend on how the heap orders things
+    order: u32,
+    cost: u32,
+    pos: (u16, u16),
+    goal: bool,
+}
+
+impl Ord for Node {
+    // BinaryHeap pops the largest, so everything is reversed
+    fn cmp(&self, other: &Node) -> Ordering {
+        other.estimate.cmp(&self.estimate)
+            .then(other.heuristic.cmp(&self.heuristic))
+            .then(other.order.cmp(&self.order))
+    }
+}
+
+impl PartialOrd for Node {
+    fn partial_cmp(&self, other: &Node) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Find a path starting at `from`.
+///
+/// `cost` gives the cost of entering the block at a position, or None if it can't be entered.
+/// `heuristic` estimates the cost left from a position to the goal, or None if the position is a
+/// goal. As long as the heuristic never overestimates, the path found is the cheapest one.
+///
+/// Positions that are `occupied` (usually `World::is_blocked`) can't be entered unless they are a
+/// goal. At most `budget` positions are expanded; if no goal has been found by then, the path to
+/// the position with the lowest heuristic is returned instead.
+pub fn find_path(
+    world: &World,
+    from: (u16, u16),
+    occupied: impl Fn((u16, u16)) -> bool,
+    cost: impl Fn(&Block, (u16, u16)) -> Option<u32>,
+    heuristic: impl Fn((u16, u16)) -> Option<u32>,
+    budget: usize,
+    ) ->
+    Vec<MoveDir>
+{
+    let width = world.blocks.len();
+    let height = world.blocks.get(0).map(|col| col.len()).unwrap_or(0);
+    let in_world = |pos: (u16, u16)| (pos.0 as usize) < width && (pos.1 as usize) < height;
+
+    if !in_world(from) {
+        return vec![];
+    }
+    let start_heuristic = match heuristic(from) {
+        Some(heur) => heur,
+        None => return vec![],
+    };
+
+    let mut done = Grid::new(width, height);
+    let mut best_cost = vec![u32::MAX; width * height];
+    let mut came_from: Vec<Option<MoveDir>> = vec![None; width * height];
+
+    let mut open = BinaryHeap::new();
+    let mut order = 0;
+    open.push(Node {
+        estimate: start_heuristic,
+        heuristic: start_heuristic,
+        order: order,
+        cost: 0,
+        pos: from,
+        goal: false,
+    });
+    best_cost[from.0 as usize * height + from.1 as usize] = 0;
+
+    let mut closest = (start_heuristic, from);
+    let mut expanded = 0;
+
+    while let Some(node) = open.pop() {
+        if node.goal {
+            return trace(&came_from, height, from, node.pos);
+        }
+        if !done.insert(node.pos) {
+            // Already reached more cheaply
+            continue;
+        }
+
+        expanded += 1;
+        if expanded > budget {
+            break;
+        }
+
+        for dir in &DIRECTIONS {
+            let new_pos = dir.move_vec(node.pos);
+            if !in_world(new_pos) || done.contains(new_pos) {
+                continue;
+            }
+            let idx = new_pos.0 as usize * height + new_pos.1 as usize;
+
+            let step = match cost(&world.blocks[new_pos.0 as usize][new_pos.1 as usize], new_pos) {
+                Some(step) => step,
+                None => continue,
+            };
+            let new_cost = node.cost.saturating_add(step);
+            if new_cost >= best_cost[idx] {
+                continue;
+            }
+
+            let heur = heuristic(new_pos);
+            if heur.is_some() && occupied(new_pos) {
+                continue;
+            }
+
+            best_cost[idx] = new_cost;
+            came_from[idx] = Some(*dir);
+            order += 1;
+
+            let heur_val = heur.unwrap_or(0);
+            if heur_val < closest.0 {
+                closest = (heur_val, new_pos);
+            }
+
+            open.push(Node {
+                estimate: new_cost.saturating_add(heur_val),
+                heuristic: heur_val,
+                order: order,
+                cost: new_cost,
+                pos: new_pos,
+                goal: heur.is_none(),
+            });
+        }
+    }
+
+    trace(&came_from, height, from, closest.1)
+}
+
+fn trace(came_from: &[Option<MoveDir>], height: usize, from: (u16, u16), to: (u16, u16)) -> Vec<MoveDir> {
+    let mut path = vec![];
+    let mut pos = to;
+
+    while pos != from {
+        match came_from[pos.0 as usize * height + pos.1 as usize] {
+            Some(dir) => {
+                let (dx, dy) = dir.to_vec();
+                pos = (pos.0.wrapping_sub(dx as u16), pos.1.wrapping_sub(dy as u16));
+                path.push(dir);
+            }
+            None => break,
+        }
+    }
+
+    path.reverse();
+    path
+}
@@ -0,0 +1,179 @@
+// Recording the keys pressed during a run so it can be played back exactly.
+//
+// Since everything random in a world comes from its seed, a run is fully described by the
+// difficulty, the seed, the key bindings and which keys were pressed and released on which frame.
+// Version 1 recordings have no key bindings, they were made with the defaults. Before version 3
+// the difficulty was always a preset.
+
+use key::Key;
+use difficulty::Difficulty;
+use controls::{self, Action, Binding};
+use save::{Writer, Reader, LoadError};
+
+const MAGIC: &[u8] = b"SREC";
+pub const RECORDING_VERSION: u8 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyEvent {
+    Down, Up
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recording {
+    pub difficulty: Difficulty,
+    pub seed: u32,
+    pub bindings: Vec<(Action, Binding)>,
+    pub events: Vec<(u64, KeyEvent, Key)>,
+}
+
+impl Recording {
+    pub fn new(difficulty: Difficulty, seed: u32, bindings: Vec<(Action, Binding)>) -> Recording {
+        Recording {
+            difficulty: difficulty,
+            seed: seed,
+            bindings: bindings,
+            events: vec![],
+        }
+    }
+
+    pub fn record(&mut self, frame: u64, event: KeyEvent, key: Key) {
+        self.events.push((frame, event, key));
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut w = Writer { buf: MAGIC.to_vec() };
+        w.u8(RECORDING_VERSION);
+        w.difficulty(&self.difficulty);
+        w.u32(self.seed);
+        w.str(&controls::bindings_to_string(&self.bindings));
+        w.u32(self.events.len() as u32);
+        for (frame, event, key) in &self.events {
+            w.u64(*frame);
+            w.u8(match event { KeyEvent::Down => 0, KeyEvent::Up => 1 });
+            write_key(&mut w, key);
+        }
+        w.buf
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Recording, LoadError> {
+        if data.len() < MAGIC.len() || &data[..MAGIC.len()] != MAGIC {
+            return Err(LoadError::NotARecording);
+        }
+        let mut r = Reader { buf: &data[MAGIC.len()..], version: RECORDING_VERSION };
+
+        let version = r.u8()?;
+        if version == 0 || version > RECORDING_VERSION {
+            return Err(LoadError::UnknownVersion(version));
+        }
+
+        let difficulty = r.difficulty()?;
+        let seed = r.u32()?;
+        let bindings = if version >= 2 {
+            controls::parse_bindings(&controls::default_bindings(), &r.str()?)
+                .map_err(|e| LoadError::BadBindings(e.to_string()))?
+        } else {
+            controls::default_bindings()
+        };
+        let mut rec = Recording::new(difficulty, seed, bindings);
+
+        let len = r.u32()?;
+        for _ in 0..len {
+            let frame = r.u64()?;
+            let event = if r.u8()? == 0 { KeyEvent::Down } else { KeyEvent::Up };
+            rec.record(frame, event, read_key(&mut r)?);
+        }
+        Ok(rec)
+    }
+}
+
+/// A recording being played back
+pub struct Replay {
+    pub recording: Recording,
+    next: usize,
+}
+
+impl Replay {
+    pub fn new(recording: Recording) -> Replay {
+        Replay { recording: recording, next: 0 }
+    }
+
+    /// Events that should happen before the given frame is run
+    pub fn events_for(&mut self, frame: u64) -> Vec<(KeyEvent, Key)> {
+        let mut res = vec![];
+        while let Some(&(at, event, ref key)) = self.recording.events.get(self.next) {
+            if at > frame {
+                break;
+            }
+            res.push((event, key.clone()));
+            self.next += 1;
+        }
+        res
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.next >= self.recording.events.len()
+    }
+}
+
+fn write_key(w: &mut Writer, key: &Key) {
+    match key {
+        Key::Arrow(dir)  => { w.u8(0); w.dir(*dir); }
+        Key::Letter(l)   => { w.u8(1); w.u8(*l); }
+        Key::Digit(d)    => { w.u8(2); w.u8(*d); }
+        Key::Shift       => w.u8(3),
+        Key::Alt         => w.u8(4),
+        Key::Ctrl        => w.u8(5),
+        Key::Meta        => w.u8(6),
+        Key::Enter       => w.u8(7),
+        Key::Backspace   => w.u8(8),
+        Key::Tab         => w.u8(9),
+        Key::Plus        => w.u8(10),
+        Key::Minus       => w.u8(11),
+        Key::Escape      => w.u8(12),
+        Key::Space       => w.u8(13),
+        Key::F(n)        => { w.u8(14); w.u8(*n); }
+        Key::Numpad(d)   => { w.u8(15); w.u8(*d); }
+        Key::Equals      => w.u8(16),
+        Key::Comma       => w.u8(17),
+        Key::Period      => w.u8(18),
+        Key::Slash       => w.u8(19),
+        Key::Backslash   => w.u8(20),
+        Key::Semicolon   => w.u8(21),
+        Key::Quote       => w.u8(22),
+        Key::Backquote   => w.u8(23),
+        Key::BracketLeft => w.u8(24),
+        Key::BracketRight => w.u8(25),
+    }
+}
+
+fn read_key(r: &mut Reader) -> Result<Key, LoadError> {
+    match r.u8()? {
+        0  => Ok(Key::Arrow(r.dir()?)),
+        1  => Ok(Key::Letter(r.u8()?)),
+        2  => Ok(Key::Digit(r.u8()?)),
+        3  => Ok(Key::Shift),
+        4  => Ok(Key::Alt),
+        5  => Ok(Key::Ctrl),
+        6  => Ok(Key::Meta),
+        7  => Ok(Key::Enter),
+        8  => Ok(Key::Backspace),
+        9  => Ok(Key::Tab),
+        10 => Ok(Key::Plus),
+        11 => Ok(Key::Minus),
+        12 => Ok(Key::Escape),
+        13 => Ok(Key::Space),
+        14 => Ok(Key::F(r.u8()?)),
+        15 => Ok(Key::Numpad(r.u8()?)),
+        16 => Ok(Key::Equals),
+        17 => Ok(Key::Comma),
+        18 => Ok(Key::Period),
+        19 => Ok(Key::Slash),
+        20 => Ok(Key::Backslash),
+        21 => Ok(Key::Semicolon),
+        22 => Ok(Key::Quote),
+        23 => Ok(Key::Backquote),
+        24 => Ok(Key::BracketLeft),
+        25 => Ok(Key::BracketRight),
+        k  => Err(LoadError::UnknownKey(k)),
+    }
+}
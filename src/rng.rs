@@ -0,0 +1,39 @@
+/// A small seedable random number generator (xorshift64*). Everything random that happens inside a
+/// world goes through the world's Rng, so the same seed always plays out the same way.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u32) -> Rng {
+        // Spread the seed out over all bits (splitmix64), xorshift doesn't like mostly-zero states
+        let mut z = (seed as u64).wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+
+        Rng { state: if z == 0 { 1 } else { z } }
+    }
+
+    /// Continue from a state previously returned by `state`
+    pub fn from_state(state: u64) -> Rng {
+        Rng { state: if state == 0 { 1 } else { state } }
+    }
+
+    pub fn state(&self) -> u64 { self.state }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.state = x;
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// A random number in [0, 1), like Math.random
+    pub fn rand(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
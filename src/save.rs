@@ -0,0 +1,483 @@
+// Saving and loading a running world.
+//
+// A save is the magic bytes "SSIM", a version byte and then the world. All numbers are little
+// endian. Blocks are stored as a palette of block ids followed by one palette index per block,
+// entities are stored as a tag byte followed by their fields.
+//
+// Version 1 saves named blocks by their display name instead of their id. Before version 3 the
+// player had no switched off tools and item drops had no age, and before version 4 there were no
+// stats at the end. Before version 5 the difficulty was always a preset. Versions 1 and 2 had no
+// limit on the player's inventory, what doesn't fit any more is dropped where the player stands.
+//
+// The difficulty is the index of its preset, followed by the value of every parameter if it's
+// custom.
+
+use world::{World, MetaAction};
+use block::{self, Block};
+use entity::{EntityWrapper, Player, Josef, Police, Bomb, Bullet, ItemDrop};
+use inventory::{InventoryItem, Inventory, PLAYER_SLOTS};
+use difficulty::{Difficulty, Preset, PARAMS};
+use move_dir::MoveDir;
+use rng::Rng;
+use stats::Stats;
+
+use std::fmt::{Display, Formatter, Error};
+use std::sync::mpsc::Sender;
+
+const MAGIC: &[u8] = b"SSIM";
+pub const SAVE_VERSION: u8 = 5;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadError {
+    NotASave,
+    UnknownVersion(u8),
+    UnexpectedEnd,
+    BadString,
+    UnknownBlock(String),
+    BadPaletteIndex(u16),
+    UnknownItem(u8),
+    UnknownEntity(u8),
+    UnknownDifficulty(u8),
+    UnknownDirection(u8),
+    UnknownKey(u8),
+    NotARecording,
+    BadBindings(String),
+}
+
+impl Display for LoadError {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
+        match self {
+            LoadError::NotASave => write!(fmt, "Not a save file"),
+            LoadError::UnknownVersion(v) => write!(fmt, "Unknown save version {}", v),
+            LoadError::UnexpectedEnd => write!(fmt, "Save file ended too early"),
+            LoadError::BadString => write!(fmt, "Save file contains a broken string"),
+            LoadError::UnknownBlock(name) => write!(fmt, "Unknown block {:?}", name),
+            LoadError::BadPaletteIndex(idx) => write!(fmt, "Block palette has no entry {}", idx),
+            LoadError::UnknownItem(tag) => write!(fmt, "Unknown item type {}", tag),
+            LoadError::UnknownEntity(tag) => write!(fmt, "Unknown entity type {}", tag),
+            LoadError::UnknownDifficulty(d) => write!(fmt, "Unknown difficulty {}", d),
+            LoadError::UnknownDirection(d) => write!(fmt, "Unknown direction {}", d),
+            LoadError::UnknownKey(k) => write!(fmt, "Unknown key {}", k),
+            LoadError::NotARecording => write!(fmt, "Not a recording"),
+            LoadError::BadBindings(e) => write!(fmt, "Broken key bindings: {}", e),
+        }
+    }
+}
+
+pub struct Writer {
+    pub buf: Vec<u8>,
+}
+
+impl Writer {
+    pub fn u8(&mut self, x: u8) { self.buf.push(x); }
+    pub fn u16(&mut self, x: u16) { self.u8(x as u8); self.u8((x >> 8) as u8); }
+    pub fn u32(&mut self, x: u32) { self.u16(x as u16); self.u16((x >> 16) as u16); }
+    pub fn u64(&mut self, x: u64) { self.u32(x as u32); self.u32((x >> 32) as u32); }
+    pub fn i16(&mut self, x: i16) { self.u16(x as u16); }
+    pub fn pos(&mut self, pos: (u16, u16)) { self.u16(pos.0); self.u16(pos.1); }
+
+    pub fn str(&mut self, x: &str) {
+        self.u32(x.len() as u32);
+        self.buf.extend_from_slice(x.as_bytes());
+    }
+
+    pub fn dir(&mut self, dir: MoveDir) {
+        self.u8(match dir {
+            MoveDir::Up => 0,
+            MoveDir::Left => 1,
+            MoveDir::Down => 2,
+            MoveDir::Right => 3,
+        });
+    }
+
+    pub fn dirs(&mut self, dirs: &[MoveDir]) {
+        self.u32(dirs.len() as u32);
+        for dir in dirs {
+            self.dir(*dir);
+        }
+    }
+
+    pub fn difficulty(&mut self, difficulty: &Difficulty) {
+        self.u8(difficulty.preset as u8);
+        if difficulty.preset == Preset::Custom {
+            for &param in PARAMS {
+                self.u16(difficulty.get(param));
+            }
+        }
+    }
+
+    fn stats(&mut self, stats: &Stats) {
+        self.u64(stats.ticks);
+        self.u64(stats.police_killed);
+        self.u64(stats.blocks_mined);
+        self.u64(stats.blocks_placed);
+        self.u64(stats.items_crafted);
+        self.u32(stats.damage.len() as u32);
+        for (source, amount) in &stats.damage {
+            self.str(source);
+            self.u64(*amount);
+        }
+    }
+
+    fn item(&mut self, item: &InventoryItem) {
+        match item {
+            InventoryItem::Block(block) => { self.u8(0); self.str(&block.id); }
+            InventoryItem::Bomb => self.u8(1),
+            InventoryItem::Bullet => self.u8(2),
+            InventoryItem::SuperBoots(dur, max) => { self.u8(3); self.u16(*dur); self.u16(*max); }
+            InventoryItem::Pickaxe(dur, max) => { self.u8(4); self.u16(*dur); self.u16(*max); }
+        }
+    }
+
+    fn entity(&mut self, entity: &EntityWrapper) {
+        match entity {
+            EntityWrapper::WPlayer(player) => {
+                self.u8(0);
+                self.pos(player.pos);
+                self.u32(player.inventory.slots().len() as u32);
+                for (item, count) in player.inventory.slots() {
+                    self.item(item);
+                    self.u64(*count);
+                }
+                self.u32(player.inventory.active as u32);
+                self.u16(player.hunger);
+                self.u32(player.inventory.tools_off().len() as u32);
+                for id in player.inventory.tools_off() {
+                    self.str(id);
+                }
+            }
+            EntityWrapper::WJosef(josef) => {
+                self.u8(1);
+                self.pos(josef.pos);
+                self.u16(josef.police_countdown);
+                self.u16(josef.police_speed);
+                self.u16(josef.walk_countdown);
+                self.u16(josef.walk_speed);
+                self.dirs(&josef.path);
+                self.u16(josef.health);
+            }
+            EntityWrapper::WPolice(police) => {
+                self.u8(2);
+                self.pos(police.pos);
+                self.u16(police.walk_countdown);
+                self.u16(police.walk_speed);
+                self.u16(police.hurt_countdown);
+                self.u16(police.hurt_speed);
+                self.dirs(&police.path);
+            }
+            EntityWrapper::WBomb(bomb) => {
+                self.u8(3);
+                self.pos(bomb.pos);
+                self.u16(bomb.countdown);
+                self.u16(bomb.explode_time);
+            }
+            EntityWrapper::WBullet(bullet) => {
+                self.u8(4);
+                self.pos(bullet.pos);
+                self.dir(bullet.dir);
+            }
+            EntityWrapper::WItemDrop(drop) => {
+                self.u8(5);
+                self.pos(drop.pos);
+                self.item(&drop.item);
+                self.u64(drop.count);
+                self.u16(drop.age);
+            }
+        }
+    }
+}
+
+pub struct Reader<'a> {
+    pub buf: &'a [u8],
+    // Of the format being read
+    pub version: u8,
+}
+
+impl<'a> Reader<'a> {
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8], LoadError> {
+        if self.buf.len() < len {
+            return Err(LoadError::UnexpectedEnd);
+        }
+        let (res, rest) = self.buf.split_at(len);
+        self.buf = rest;
+        Ok(res)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, LoadError> { Ok(self.bytes(1)?[0]) }
+    pub fn u16(&mut self) -> Result<u16, LoadError> { Ok(self.u8()? as u16 | (self.u8()? as u16) << 8) }
+    pub fn u32(&mut self) -> Result<u32, LoadError> { Ok(self.u16()? as u32 | (self.u16()? as u32) << 16) }
+    pub fn u64(&mut self) -> Result<u64, LoadError> { Ok(self.u32()? as u64 | (self.u32()? as u64) << 32) }
+    pub fn i16(&mut self) -> Result<i16, LoadError> { Ok(self.u16()? as i16) }
+    pub fn pos(&mut self) -> Result<(u16, u16), LoadError> { Ok((self.u16()?, self.u16()?)) }
+
+    pub fn str(&mut self) -> Result<String, LoadError> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.bytes(len)?.to_vec()).map_err(|_| LoadError::BadString)
+    }
+
+    pub fn dir(&mut self) -> Result<MoveDir, LoadError> {
+        match self.u8()? {
+            0 => Ok(MoveDir::Up),
+            1 => Ok(MoveDir::Left),
+            2 => Ok(MoveDir::Down),
+            3 => Ok(MoveDir::Right),
+            d => Err(LoadError::UnknownDirection(d)),
+        }
+    }
+
+    pub fn dirs(&mut self) -> Result<Vec<MoveDir>, LoadError> {
+        let len = self.u32()?;
+        let mut res = vec![];
+        for _ in 0..len {
+            res.push(self.dir()?);
+        }
+        Ok(res)
+    }
+
+    pub fn difficulty(&mut self) -> Result<Difficulty, LoadError> {
+        let idx = self.u8()?;
+        if idx != Preset::Custom as u8 {
+            return Difficulty::from_index(idx).ok_or(LoadError::UnknownDifficulty(idx));
+        }
+        let mut difficulty = Difficulty::EASY.custom();
+        for &param in PARAMS {
+            difficulty.set(param, self.u16()?);
+        }
+        Ok(difficulty)
+    }
+
+    fn stats(&mut self) -> Result<Stats, LoadError> {
+        let mut stats = Stats::default();
+        stats.ticks = self.u64()?;
+        stats.police_killed = self.u64()?;
+        stats.blocks_mined = self.u64()?;
+        stats.blocks_placed = self.u64()?;
+        stats.items_crafted = self.u64()?;
+        for _ in 0..self.u32()? {
+            let source = self.str()?;
+            stats.damage.push((source, self.u64()?));
+        }
+        Ok(stats)
+    }
+
+    fn block(&mut self) -> Result<Block, LoadError> {
+        let id = self.str()?;
+        if self.version == 1 {
+            block::by_name(&id).ok_or(LoadError::UnknownBlock(id))
+        } else {
+            block::by_id(&id).ok_or(LoadError::UnknownBlock(id))
+        }
+    }
+
+    fn item(&mut self) -> Result<InventoryItem, LoadError> {
+        match self.u8()? {
+            0 => Ok(InventoryItem::Block(self.block()?)),
+            1 => Ok(InventoryItem::Bomb),
+            2 => Ok(InventoryItem::Bullet),
+            3 => Ok(InventoryItem::SuperBoots(self.u16()?, self.u16()?)),
+            4 => Ok(InventoryItem::Pickaxe(self.u16()?, self.u16()?)),
+            tag => Err(LoadError::UnknownItem(tag)),
+        }
+    }
+
+    // Also returns the items that didn't fit in the player's inventory, which older saves had no
+    // limit on
+    fn entity(&mut self) -> Result<(EntityWrapper, Vec<(InventoryItem, u64)>), LoadError> {
+        let mut left = vec![];
+        let entity = match self.u8()? {
+            0 => {
+                let mut player = Player::new(self.pos()?, 0);
+                let slots = self.u32()?;
+                let mut items = vec![];
+                for _ in 0..slots {
+                    let item = self.item()?;
+                    items.push((item, self.u64()?));
+                }
+                let active = self.u32()? as usize;
+                let active_item = items.get(active).map(|(item, _)| item.clone());
+
+                player.inventory = Inventory::new(PLAYER_SLOTS);
+                for (item, count) in items {
+                    let didnt_fit = player.inventory.add(item.clone(), count);
+                    if didnt_fit > 0 {
+                        left.push((item, didnt_fit));
+                    }
+                }
+                // Slots may have been split up differently, so find the item that was active
+                let slots = player.inventory.slots().len();
+                player.inventory.active = player.inventory.slots().iter()
+                    .position(|(item, _)| Some(item) == active_item.as_ref())
+                    .unwrap_or(active.min(slots.saturating_sub(1)));
+
+                player.hunger = self.u16()?;
+                if self.version >= 3 {
+                    for _ in 0..self.u32()? {
+                        let id = self.str()?;
+                        player.inventory.toggle_tool(&id);
+                    }
+                }
+                EntityWrapper::WPlayer(player)
+            }
+            1 => {
+                let mut josef = Josef::new(self.pos()?, 0, 0, 0);
+                josef.police_countdown = self.u16()?;
+                josef.police_speed = self.u16()?;
+                josef.walk_countdown = self.u16()?;
+                josef.walk_speed = self.u16()?;
+                josef.path = self.dirs()?;
+                josef.health = self.u16()?;
+                EntityWrapper::WJosef(josef)
+            }
+            2 => {
+                let mut police = Police::new(self.pos()?, 0, 0);
+                police.walk_countdown = self.u16()?;
+                police.walk_speed = self.u16()?;
+                police.hurt_countdown = self.u16()?;
+                police.hurt_speed = self.u16()?;
+                police.path = self.dirs()?;
+                EntityWrapper::WPolice(police)
+            }
+            3 => {
+                let mut bomb = Bomb::new(self.pos()?, 0);
+                bomb.countdown = self.u16()?;
+                bomb.explode_time = self.u16()?;
+                EntityWrapper::WBomb(bomb)
+            }
+            4 => {
+                let pos = self.pos()?;
+                EntityWrapper::WBullet(Bullet::new(pos, self.dir()?))
+            }
+            5 => {
+                let pos = self.pos()?;
+                let item = self.item()?;
+                let mut drop = ItemDrop::new(pos, item, self.u64()?);
+                if self.version >= 3 {
+                    drop.age = self.u16()?;
+                }
+                EntityWrapper::WItemDrop(drop)
+            }
+            tag => return Err(LoadError::UnknownEntity(tag)),
+        };
+        Ok((entity, left))
+    }
+}
+
+pub fn save_world(world: &World) -> Vec<u8> {
+    let mut w = Writer { buf: MAGIC.to_vec() };
+    w.u8(SAVE_VERSION);
+
+    w.difficulty(&world.difficulty);
+    w.u32(world.seed);
+    w.u64(world.rng.state());
+    w.i16(world.scroll.0);
+    w.i16(world.scroll.1);
+    w.dirs(&world.auto_walk);
+    w.dirs(&world.auto_mine);
+
+    // Blocks
+    let mut palette: Vec<Block> = vec![];
+    let mut indices = vec![];
+    for col in &world.blocks {
+        for block in col {
+            let idx = match palette.iter().position(|b| b == block) {
+                Some(idx) => idx,
+                None => {
+                    palette.push(block.clone());
+                    palette.len() - 1
+                }
+            };
+            indices.push(idx as u16);
+        }
+    }
+    w.u32(palette.len() as u32);
+    for block in &palette {
+        w.str(&block.id);
+    }
+    w.u32(world.blocks.len() as u32);
+    w.u32(world.blocks.get(0).map(|col| col.len()).unwrap_or(0) as u32);
+    for idx in indices {
+        w.u16(idx);
+    }
+
+    // Entities
+    w.u32(world.entities.len() as u32);
+    for (id, entity) in &world.entities {
+        w.u64(*id);
+        w.entity(entity);
+    }
+
+    w.stats(&world.stats);
+
+    w.buf
+}
+
+pub fn load_world(data: &[u8], action_sender: Sender<MetaAction>) -> Result<World, LoadError> {
+    if data.len() < MAGIC.len() || &data[..MAGIC.len()] != MAGIC {
+        return Err(LoadError::NotASave);
+    }
+    let mut r = Reader { buf: &data[MAGIC.len()..], version: SAVE_VERSION };
+
+    let version = r.u8()?;
+    if version == 0 || version > SAVE_VERSION {
+        return Err(LoadError::UnknownVersion(version));
+    }
+    r.version = version;
+
+    let difficulty = r.difficulty()?;
+    let seed = r.u32()?;
+
+    let mut world = World::empty(difficulty, seed, action_sender);
+    world.rng = Rng::from_state(r.u64()?);
+    world.scroll = (r.i16()?, r.i16()?);
+    world.auto_walk = r.dirs()?;
+    world.auto_mine = r.dirs()?;
+
+    // Blocks
+    let palette_len = r.u32()?;
+    let mut palette = vec![];
+    for _ in 0..palette_len {
+        palette.push(r.block()?);
+    }
+    let (width, height) = (r.u32()?, r.u32()?);
+    for _ in 0..width {
+        let mut col = vec![];
+        for _ in 0..height {
+            let idx = r.u16()?;
+            col.push(palette.get(idx as usize).cloned().ok_or(LoadError::BadPaletteIndex(idx))?);
+        }
+        world.blocks.push(col);
+    }
+
+    // Entities
+    let entities = r.u32()?;
+    let mut dropped = vec![];
+    for _ in 0..entities {
+        let id = r.u64()?;
+        let (entity, left) = r.entity()?;
+        let pos = entity.get_pos();
+        world.insert_entity(id, entity);
+        dropped.extend(left.into_iter().map(|(item, count)| (pos, item, count)));
+    }
+
+    // Drop what didn't fit where the player is, one drop for each item
+    let mut drops: Vec<ItemDrop> = vec![];
+    for (pos, item, count) in dropped {
+        match drops.iter_mut().find(|drop| drop.pos == pos && drop.item == item) {
+            Some(drop) => drop.count += count,
+            None => drops.push(ItemDrop::new(pos, item, count)),
+        }
+    }
+    for drop in drops {
+        let mut id = world.entities.keys().next_back().map(|id| id.wrapping_add(1)).unwrap_or(0);
+        while world.entities.contains_key(&id) {
+            id = id.wrapping_add(1);
+        }
+        world.insert_entity(id, EntityWrapper::WItemDrop(drop));
+    }
+
+    if version >= 4 {
+        world.stats = r.stats()?;
+    }
+
+    Ok(world)
+}
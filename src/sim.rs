@@ -0,0 +1,107 @@
+// Running worlds without drawing them, for tools that want to play a lot of games quickly.
+
+use world::{World, MetaAction, WORLD_SIZE};
+use block::Block;
+use entity::{EntityWrapper, Player, Josef};
+use difficulty::Difficulty;
+use controls::Action;
+
+use std::sync::mpsc::{Receiver, channel};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Died, Won
+}
+
+/// A world that is only ever ticked, never drawn
+pub struct Simulation {
+    pub world: World,
+    action_receiver: Receiver<MetaAction>,
+    outcome: Option<Outcome>,
+    ticks: u64,
+}
+
+impl Simulation {
+    /// Generate a world of the normal size
+    pub fn new(difficulty: Difficulty, seed: u32) -> Simulation {
+        Simulation::with_size(difficulty, seed, WORLD_SIZE.0, WORLD_SIZE.1)
+    }
+
+    pub fn with_size(difficulty: Difficulty, seed: u32, width: usize, height: usize) -> Simulation {
+        let (send, recv) = channel();
+        let mut world = World::empty(difficulty, seed, send);
+        world.generate(width, height);
+
+        Simulation {
+            world: world,
+            action_receiver: recv,
+            outcome: None,
+            ticks: 0,
+        }
+    }
+
+    /// Do something as the player
+    pub fn apply(&mut self, action: &Action) {
+        if self.outcome.is_none() {
+            self.world.do_action(action);
+            self.check_outcome();
+        }
+    }
+
+    /// Tick the world up to n times, stopping early if the game ends
+    pub fn step(&mut self, n: u64) -> Option<Outcome> {
+        for _ in 0..n {
+            if self.outcome.is_some() {
+                break;
+            }
+            self.world.tick();
+            self.ticks += 1;
+            self.check_outcome();
+        }
+        self.outcome
+    }
+
+    fn check_outcome(&mut self) {
+        while let Ok(action) = self.action_receiver.try_recv() {
+            if self.outcome.is_none() {
+                self.outcome = Some(match action {
+                    MetaAction::Die => Outcome::Died,
+                    MetaAction::Win => Outcome::Won,
+                });
+            }
+        }
+    }
+
+    /// How the game ended, if it has
+    pub fn outcome(&self) -> Option<Outcome> { self.outcome }
+
+    /// How many times the world has been ticked
+    pub fn ticks(&self) -> u64 { self.ticks }
+
+    pub fn entities<'a>(&'a self) -> impl Iterator<Item=(u64, &'a EntityWrapper)> + 'a {
+        self.world.entities.iter().map(|(id, en)| (*id, en))
+    }
+
+    pub fn player(&self) -> Option<&Player> {
+        match self.world.get_player_id().and_then(|id| self.world.entities.get(&id)) {
+            Some(EntityWrapper::WPlayer(ref player)) => Some(player),
+            _ => None,
+        }
+    }
+
+    pub fn josef(&self) -> Option<&Josef> {
+        self.world.entities.values()
+            .filter_map(|en| if let EntityWrapper::WJosef(ref josef) = en { Some(josef) } else { None })
+            .next()
+    }
+
+    pub fn police_count(&self) -> usize {
+        self.world.entities.values()
+            .filter(|en| if let EntityWrapper::WPolice(_) = en { true } else { false })
+            .count()
+    }
+
+    pub fn block_at(&self, pos: (u16, u16)) -> Option<&Block> {
+        self.world.blocks.get(pos.0 as usize).and_then(|col| col.get(pos.1 as usize))
+    }
+}
@@ -0,0 +1,103 @@
+// Which entities are where, so finding what's at a position doesn't mean going through all of them.
+
+use std::collections::HashMap;
+
+#[derive(Default)]
+pub struct SpatialIndex {
+    cells: HashMap<(u16, u16), Vec<u64>>,
+}
+
+impl SpatialIndex {
+    pub fn new() -> SpatialIndex {
+        SpatialIndex::default()
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+    }
+
+    pub fn insert(&mut self, id: u64, pos: (u16, u16)) {
+        self.cells.entry(pos).or_insert_with(Vec::new).push(id);
+    }
+
+    pub fn remove(&mut self, id: u64, pos: (u16, u16)) {
+        let now_empty =
+            if let Some(ids) = self.cells.get_mut(&pos) {
+                ids.retain(|x| *x != id);
+                ids.is_empty()
+            } else {
+                false
+            };
+        if now_empty {
+            self.cells.remove(&pos);
+        }
+    }
+
+    pub fn move_to(&mut self, id: u64, from: (u16, u16), to: (u16, u16)) {
+        if from != to {
+            self.remove(id, from);
+            self.insert(id, to);
+        }
+    }
+
+    /// Entities at a position, lowest id first like `World::entities`
+    pub fn at(&self, pos: (u16, u16)) -> Vec<u64> {
+        let mut ids = self.cells.get(&pos).cloned().unwrap_or(vec![]);
+        ids.sort();
+        ids
+    }
+
+    pub fn is_occupied(&self, pos: (u16, u16)) -> bool {
+        self.cells.contains_key(&pos)
+    }
+
+    /// Entities with min <= pos <= max, lowest id first
+    pub fn in_rect(&self, min: (u16, u16), max: (u16, u16)) -> Vec<u64> {
+        let mut ids: Vec<u64> = self.cells_in_rect(min, max).into_iter()
+            .flat_map(|(_, at)| at.iter().cloned())
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    /// Entities closer than radius to center, lowest id first
+    pub fn within(&self, center: (u16, u16), radius: u16) -> Vec<u64> {
+        let min = (center.0.saturating_sub(radius), center.1.saturating_sub(radius));
+        let max = (center.0.saturating_add(radius), center.1.saturating_add(radius));
+
+        let mut ids: Vec<u64> = self.cells_in_rect(min, max).into_iter()
+            .filter(|(pos, _)| {
+                let (dx, dy) = (pos.0 as i64 - center.0 as i64, pos.1 as i64 - center.1 as i64);
+                dx * dx + dy * dy < radius as i64 * radius as i64
+            })
+            .flat_map(|(_, at)| at.iter().cloned())
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    fn cells_in_rect(&self, min: (u16, u16), max: (u16, u16)) -> Vec<((u16, u16), &Vec<u64>)> {
+        if min.0 > max.0 || min.1 > max.1 {
+            return vec![];
+        }
+
+        let area = ((max.0 - min.0) as usize + 1) * ((max.1 - min.1) as usize + 1);
+        if area > self.cells.len() {
+            // Fewer occupied positions than positions in the rect
+            self.cells.iter()
+                .filter(|(pos, _)| pos.0 >= min.0 && pos.0 <= max.0 && pos.1 >= min.1 && pos.1 <= max.1)
+                .map(|(pos, at)| (*pos, at))
+                .collect()
+        } else {
+            let mut res = vec![];
+            for x in min.0..=max.0 {
+                for y in min.1..=max.1 {
+                    if let Some(at) = self.cells.get(&(x, y)) {
+                        res.push(((x, y), at));
+                    }
+                }
+            }
+            res
+        }
+    }
+}
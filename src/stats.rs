@@ -0,0 +1,56 @@
+use std::fmt::Write;
+
+/// Like "1:05", at 60 ticks a second
+pub fn format_ticks(ticks: u64) -> String {
+    let secs = ticks / 60;
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+/// How a run went, kept by the world as it's played
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub ticks: u64,
+    pub police_killed: u64,
+    pub blocks_mined: u64,
+    pub blocks_placed: u64,
+    pub items_crafted: u64,
+    // Damage the player took, by the name of what did it
+    pub damage: Vec<(String, u64)>,
+    // None while there is no Josef, like before the world is generated
+    pub josef_health: Option<u16>,
+}
+
+impl Stats {
+    pub fn add_damage(&mut self, source: &str, amount: u64) {
+        match self.damage.iter_mut().find(|(other, _)| other == source) {
+            Some((_, total)) => *total += amount,
+            None => self.damage.push((source.to_string(), amount)),
+        }
+    }
+
+    pub fn damage_taken(&self) -> u64 {
+        self.damage.iter().map(|(_, amount)| amount).sum()
+    }
+
+    pub fn time(&self) -> String {
+        format_ticks(self.ticks)
+    }
+
+    /// The stats as "key = value" lines, for the host to keep
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        let _ = writeln!(text, "ticks = {}", self.ticks);
+        let _ = writeln!(text, "police_killed = {}", self.police_killed);
+        let _ = writeln!(text, "blocks_mined = {}", self.blocks_mined);
+        let _ = writeln!(text, "blocks_placed = {}", self.blocks_placed);
+        let _ = writeln!(text, "items_crafted = {}", self.items_crafted);
+        let _ = writeln!(text, "damage_taken = {}", self.damage_taken());
+        for (source, amount) in &self.damage {
+            let _ = writeln!(text, "damage_from_{} = {}", source.to_lowercase().replace(' ', "_"), amount);
+        }
+        if let Some(health) = self.josef_health {
+            let _ = writeln!(text, "josef_health = {}", health);
+        }
+        text
+    }
+}
@@ -1,22 +1,36 @@
-use std::i32;
-
 use ext::*;
 use controls::Action;
 use block;
 use entity;
-use entity::{EntityWrapper, Player, Josef};
+use entity::{EntityWrapper, Player, Josef, ItemDrop};
 use shape::Shape;
 use difficulty::Difficulty;
 use inventory::InventoryItem;
-use move_dir::{MoveDir, random_dir, DIRECTIONS};
-
-use std::collections::HashMap;
+use move_dir::{MoveDir, random_dir};
+use rng::Rng;
+use pathfinding;
+use flow::FlowField;
+use spatial::SpatialIndex;
+use stats::Stats;
+
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter, Error};
 use std::mem;
 use std::sync::mpsc::Sender;
 
+pub const WORLD_SIZE: (usize, usize) = (180, 111);
 pub const HOTBAR_HEIGHT: u16 = 5;
 pub const SCROLL_FOLLOW_DIST: i16 = 10;
 
+// How many positions the player's path finding may look at
+const PATH_BUDGET: usize = 1000;
+const RUN_LENGTH: usize = 20;
+const SUPER_MINE_DIST: i32 = 30;
+// How many block changes can be undone
+const UNDO_LENGTH: usize = 5;
+// Nothing can be undone with an enemy this close to the player
+pub const UNDO_ENEMY_DIST: u16 = 8;
+
 #[derive(Debug)]
 pub enum MetaAction {
     Die, Win
@@ -24,32 +38,81 @@ pub enum MetaAction {
 
 pub struct World {
     pub blocks: Vec<Vec<block::Block>>,
-    pub entities: HashMap<u64, entity::EntityWrapper>,
+    // Add, remove and move entities through the methods below, so positions stays right
+    pub entities: BTreeMap<u64, entity::EntityWrapper>,
+    positions: SpatialIndex,
     pub difficulty: Difficulty,
+    pub seed: u32,
+    pub rng: Rng,
     pub auto_walk: Vec<MoveDir>,
     pub auto_mine: Vec<MoveDir>,
+    pub flow: FlowField,
     action_sender: Sender<MetaAction>,
     pub scroll: (i16, i16),
+    // The last blocks the player placed or broke, newest last
+    pub undo: Vec<Undo>,
+    pub stats: Stats,
+}
+
+/// A block the player placed or broke, which can be put back like it was
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Undo {
+    pub pos: (u16, u16),
+    // Where the player was standing at the time
+    pub player_pos: (u16, u16),
+    pub block: block::Block,
+    // Placed, or broken and picked up
+    pub placed: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UndoError {
+    Nothing,
+    EnemyNearby,
+    // The block or the player's inventory has changed since
+    Changed,
+}
+
+impl Display for UndoError {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
+        match self {
+            UndoError::Nothing => write!(fmt, "Nothing to undo"),
+            UndoError::EnemyNearby => write!(fmt, "Can't undo with enemies nearby"),
+            UndoError::Changed => write!(fmt, "Can't undo, things have changed since"),
+        }
+    }
 }
 
 
 impl World {
-    pub fn empty(difficulty: Difficulty, action_sender: Sender<MetaAction>) -> World {
+    pub fn empty(difficulty: Difficulty, seed: u32, action_sender: Sender<MetaAction>) -> World {
         World {
             blocks: vec![],
-            entities: HashMap::new(),
+            entities: BTreeMap::new(),
+            positions: SpatialIndex::new(),
             difficulty: difficulty,
+            seed: seed,
+            rng: Rng::new(seed),
             auto_walk: vec![],
             auto_mine: vec![],
+            flow: FlowField::new(),
             action_sender: action_sender,
             scroll: (0, 0),
+            undo: vec![],
+            stats: Stats::default(),
         }
     }
 
     pub fn tick(&mut self) {
-        for k in self.entities.clone().keys() {
-            if let Some(f) = self.entities.get(k).map(|x| x.get_tick_fn()) {
-                f(self, *k);
+        self.stats.ticks += 1;
+        if let Some(pos) = self.get_player_id().and_then(|id| self.entities.get(&id)).map(|en| en.get_pos()) {
+            self.flow.update(&self.blocks, pos);
+        }
+
+        let ids: Vec<u64> = self.entities.keys().cloned().collect();
+        for k in ids {
+            if let Some(f) = self.entities.get(&k).map(|x| x.get_tick_fn()) {
+                f(self, k);
             }
         }
 
@@ -57,27 +120,10 @@ impl World {
             if let Some(EntityWrapper::WPlayer(ref mut p)) =
                 self.get_player_id().and_then(|id| self.entities.get_mut(&id))
             {
-                let mut to_remove = None;
-                if let Some((i, (InventoryItem::SuperBoots(ref mut d, max), ref mut count))) =
-                    p.inventory.iter_mut()
-                    .enumerate()
-                    .find(|x| match (x.1).0 { InventoryItem::SuperBoots(_, _) => true, _ => false })
-                {
-                    *d -= 1;
-                    if *d == 0 {
-                        *count -= 1;
-                        *d = *max;
-                        if *count == 0 {
-                            to_remove = Some(i);
-                        }
-                    }
-                } else {
+                if !p.inventory.wear("super_boots") {
                     self.auto_walk.clear();
                     return;
                 }
-                if let Some(to_remove) = to_remove {
-                    p.inventory.remove(to_remove);
-                }
             }
 
             let dir = self.auto_walk.remove(0);
@@ -87,27 +133,10 @@ impl World {
             if let Some(EntityWrapper::WPlayer(ref mut p)) =
                 self.get_player_id().and_then(|id| self.entities.get_mut(&id))
             {
-                let mut to_remove = None;
-                if let Some((i, (InventoryItem::Pickaxe(ref mut d, max), ref mut count))) =
-                    p.inventory.iter_mut()
-                    .enumerate()
-                    .find(|x| match (x.1).0 { InventoryItem::Pickaxe(_, _) => true, _ => false })
-                {
-                    *d -= 1;
-                    if *d == 0 {
-                        *count -= 1;
-                        *d = *max;
-                        if *count == 0 {
-                            to_remove = Some(i);
-                        }
-                    }
-                } else {
+                if !p.inventory.wear("pickaxe") {
                     self.auto_mine.clear();
                     return;
                 }
-                if let Some(to_remove) = to_remove {
-                    p.inventory.remove(to_remove);
-                }
             }
 
             let dir = self.auto_mine.remove(0);
@@ -176,21 +205,26 @@ impl World {
                 if let Some(EntityWrapper::WPlayer(p)) = self.get_player_id().and_then(|id| self.entities.get(&id)) {
                     let start_pos = dir.move_vec(p.pos);
 
-                    let heur = |(x, y)| {
-                        let (dx, dy) = (x as i32 - start_pos.0 as i32, y as i32 - start_pos.1 as i32);
-                        let score = dx * dx + dy * dy;
-                        Some(-score / 3)
+                    // Dig towards a point a bit ahead, only through blocks that can be broken
+                    let (dx, dy) = dir.to_vec();
+                    let target = (
+                        start_pos.0 as i32 + dx as i32 * SUPER_MINE_DIST,
+                        start_pos.1 as i32 + dy as i32 * SUPER_MINE_DIST,
+                        );
+                    let heur = |(x, y): (u16, u16)| {
+                        let dist = (x as i32 - target.0).abs() + (y as i32 - target.1).abs();
+                        if dist == 0 { None } else { Some(dist as u32) }
                     };
-                    self.auto_mine = self.find_path(
+                    self.auto_mine = pathfinding::find_path(
+                        self,
                         start_pos,
+                        |_| false,
                         |block, _|
-                            if block.is_passable()
-                                { None }
-                                else { Some(-3) },
+                            if block.is_breakable()
+                                { Some(1) }
+                                else { None },
                         heur,
-                        1000)
-                        .into_iter()
-                        .collect();
+                        PATH_BUDGET);
 
                     self.break_dir(dir);
                 }
@@ -200,42 +234,63 @@ impl World {
                 self.auto_walk = vec![];
                 self.auto_mine = vec![];
             }
+            Action::Drop(dir) => {
+                self.get_player_id().map(|id| Player::drop(self, dir, id));
+            }
+            Action::Use(dir) => {
+                self.get_player_id().map(|id| Player::use_active(self, dir, id));
+                self.auto_walk = vec![];
+                self.auto_mine = vec![];
+            }
+            Action::SelectSlot(idx) => {
+                if let Some(EntityWrapper::WPlayer(pl)) = self.get_player_id().and_then(|id| self.entities.get_mut(&id)) {
+                    pl.inventory.select(idx);
+                }
+            }
             Action::Die => {
                 self.do_metaaction(MetaAction::Die);
             }
+            Action::Undo => {
+                let _ = self.undo();
+                self.auto_walk = vec![];
+                self.auto_mine = vec![];
+            }
             Action::IncActive => {
                 self.get_player_id()
                     .and_then(|id| self.entities.get_mut(&id))
                     .map(|en| {
                         if let &mut EntityWrapper::WPlayer(ref mut pl) = en {
-                            if pl.active < pl.inventory.len() - 1{
-                                pl.active += 1;
-                            }
+                            pl.inventory.select_next();
                         }
                     });
             }
             Action::Run(dir) => {
                 if let Some(EntityWrapper::WPlayer(p)) = self.get_player_id().and_then(|id| self.entities.get(&id)) {
                     let pos = p.pos;
-                    let heur = |(x, y)| {
-                        let score = match dir {
-                            MoveDir::Left =>  pos.0 as i32 - x as i32,
-                            MoveDir::Right => x as i32 - pos.0 as i32,
-                            MoveDir::Up =>    pos.1 as i32 - y as i32,
-                            MoveDir::Down =>  y as i32 - pos.1 as i32,
+                    let (width, height) = (self.blocks.len() as u16, self.blocks[0].len() as u16);
+
+                    // Get as close to the edge of the map in that direction as possible
+                    let heur = |(x, y): (u16, u16)| {
+                        let dist = match dir {
+                            MoveDir::Left =>  x,
+                            MoveDir::Right => width - 1 - x,
+                            MoveDir::Up =>    y,
+                            MoveDir::Down =>  height - 1 - y,
                         };
-                        Some(score * 3)
+                        if dist == 0 { None } else { Some(dist as u32) }
                     };
-                    self.auto_walk = self.find_path(
+                    self.auto_walk = pathfinding::find_path(
+                        self,
                         pos,
+                        |pos| self.is_blocked(pos),
                         |block, _|
                             if block.is_passable()
                                 { Some(1) }
                                 else { None },
                         heur,
-                        1000)
+                        PATH_BUDGET)
                         .into_iter()
-                        .take(20)
+                        .take(RUN_LENGTH)
                         .collect();
                 }
             }
@@ -244,9 +299,7 @@ impl World {
                     .and_then(|id| self.entities.get_mut(&id))
                     .map(|en| {
                         if let &mut EntityWrapper::WPlayer(ref mut pl) = en {
-                            if pl.active > 0 {
-                                pl.active -= 1;
-                            }
+                            pl.inventory.select_prev();
                         }
                     });
             }
@@ -256,9 +309,9 @@ impl World {
     }
 
     fn break_dir(&mut self, break_dir: MoveDir) {
-        let new_pos;
+        let (new_pos, pl_pos);
         if let Some(player) = self.get_player_id().and_then(|id| self.entities.get(&id)) {
-            let pl_pos = player.get_pos();
+            pl_pos = player.get_pos();
             let (dx, dy) = break_dir.to_vec();
 
             new_pos = (pl_pos.0.wrapping_add(dx as u16), pl_pos.1.wrapping_add(dy as u16));
@@ -266,30 +319,140 @@ impl World {
             return;
         }
 
-        let block_pickup =
-            if let Some(block_at) = self.blocks
-                .get_mut(new_pos.0 as usize)
-                .and_then(|x| x.get_mut(new_pos.1 as usize))
-            {
-                if block_at.is_breakable() {
-                    // Break block
-                    mem::replace(block_at, block::GROUND.clone())
-                } else {
-                    return;
-                }
-            } else {
-                return;
-            };
+        let breakable = self.blocks
+            .get(new_pos.0 as usize)
+            .and_then(|x| x.get(new_pos.1 as usize))
+            .map(|x| x.is_breakable())
+            .unwrap_or(false);
+        if !breakable {
+            return;
+        }
+        // Break block
+        let block_pickup = match self.set_block(new_pos, block::GROUND.clone()) {
+            Some(block) => block,
+            None => return,
+        };
 
+        let mut picked_up = false;
         if let Some(EntityWrapper::WPlayer(ref mut player)) =
             self.get_player_id().and_then(|x| self.entities.get_mut(&x))
         {
-            player.pick_up(InventoryItem::Block(block_pickup));
+            picked_up = player.pick_up(InventoryItem::Block(block_pickup.clone()));
+        }
+        self.stats.blocks_mined += 1;
+        if picked_up {
+            self.remember_undo(Undo {
+                pos: new_pos,
+                player_pos: pl_pos,
+                block: block_pickup,
+                placed: false,
+            });
+        } else {
+            self.add_entity(EntityWrapper::WItemDrop(ItemDrop::new(new_pos, InventoryItem::Block(block_pickup), 1)));
         }
 
         self.get_player_id().map(|id| self.move_entity(id, break_dir));
     }
 
+    pub fn remember_undo(&mut self, undo: Undo) {
+        self.undo.push(undo);
+        if self.undo.len() > UNDO_LENGTH {
+            self.undo.remove(0);
+        }
+    }
+
+    /// Take back the last block the player placed or broke. Placed blocks go back in the
+    /// inventory, broken ones are taken out of it and the player steps back to where they were.
+    pub fn undo(&mut self) -> Result<(), UndoError> {
+        let last = self.undo.last().cloned().ok_or(UndoError::Nothing)?;
+        let id = self.get_player_id().ok_or(UndoError::Nothing)?;
+        let pos = self.entities[&id].get_pos();
+
+        let enemy_near = self.entities_within(pos, UNDO_ENEMY_DIST).iter().any(|other| match self.entities.get(other) {
+            Some(EntityWrapper::WPolice(_)) | Some(EntityWrapper::WJosef(_)) => true,
+            _ => false,
+        });
+        if enemy_near {
+            return Err(UndoError::EnemyNearby);
+        }
+
+        let at = self.blocks.get(last.pos.0 as usize).and_then(|col| col.get(last.pos.1 as usize)).cloned();
+        let item = InventoryItem::Block(last.block.clone());
+        if last.placed {
+            if at.as_ref() != Some(&last.block) {
+                return Err(UndoError::Changed);
+            }
+            self.set_block(last.pos, block::GROUND.clone());
+            ItemDrop::give(self, id, item, 1);
+            self.stats.blocks_placed = self.stats.blocks_placed.saturating_sub(1);
+        } else {
+            if at.as_ref() != Some(&block::GROUND) || self.entities_at(last.pos).iter().any(|other| *other != id) {
+                return Err(UndoError::Changed);
+            }
+            // Step out of the way first
+            let step_back = pos == last.pos;
+            if step_back {
+                let free = self.blocks[last.player_pos.0 as usize][last.player_pos.1 as usize].is_passable()
+                    && !self.is_blocked(last.player_pos);
+                if !free {
+                    return Err(UndoError::Changed);
+                }
+            }
+            let taken = match self.entities.get_mut(&id) {
+                Some(EntityWrapper::WPlayer(player)) => player.inventory.take(&item, 1),
+                _ => 0,
+            };
+            if taken == 0 {
+                return Err(UndoError::Changed);
+            }
+            if step_back {
+                self.set_entity_pos(id, last.player_pos);
+            }
+            self.set_block(last.pos, last.block);
+            self.stats.blocks_mined = self.stats.blocks_mined.saturating_sub(1);
+        }
+        self.undo.pop();
+        Ok(())
+    }
+
+    /// Hurt an entity. Damage to the player is added to the stats under the name of its source.
+    pub fn hurt(&mut self, en_id: u64, amount: u16, source: &str) {
+        let hurt = match self.entities.get(&en_id) {
+            Some(en) => {
+                if let EntityWrapper::WPlayer(_) = en {
+                    self.stats.add_damage(source, amount as u64);
+                }
+                en.get_hurt_fn()
+            }
+            None => return,
+        };
+        hurt(self, en_id, amount);
+    }
+
+    /// The stats so far, with Josef's health as it is now
+    pub fn run_stats(&self) -> Stats {
+        let mut stats = self.stats.clone();
+        let josef = self.entities.values().filter_map(|en| match en {
+            EntityWrapper::WJosef(josef) => Some(josef.health),
+            _ => None,
+        }).next();
+        if josef.is_some() {
+            stats.josef_health = josef;
+        }
+        stats
+    }
+
+    /// Change the block at a position. Anything that changes blocks after the world is generated
+    /// should go through here, so the flow field stays up to date.
+    pub fn set_block(&mut self, pos: (u16, u16), block: block::Block) -> Option<block::Block> {
+        let old = match self.blocks.get_mut(pos.0 as usize).and_then(|col| col.get_mut(pos.1 as usize)) {
+            Some(at) => mem::replace(at, block),
+            None => return None,
+        };
+        self.flow.block_changed(&self.blocks, pos);
+        Some(old)
+    }
+
     fn move_entity(&mut self, en_id: u64, move_dir: MoveDir) -> bool {
         if let Some(en) = self.entities.get(&en_id) {
             en.get_move_fn()(self, en_id, move_dir)
@@ -342,13 +505,15 @@ impl World {
     pub fn generate(&mut self, width: usize, height: usize) {
         log("Generating!");
 
-        self.entities = HashMap::new();
+        self.entities = BTreeMap::new();
+        self.positions.clear();
         self.blocks = vec![];
+        self.flow.invalidate();
 
         for x in 0..width {
             self.blocks.push(vec![]);
             for _ in 0..height {
-                if rand() > 0.1 {
+                if self.rng.rand() > 0.1 {
                     self.blocks[x].push(block::WALL.clone());
                 } else {
                     self.blocks[x].push(block::STONE.clone());
@@ -358,17 +523,17 @@ impl World {
 
         let mut placed = vec![];
         for _ in 0..10 * width * height {
-            if rand() < 0.01 || placed.is_empty() {
-                let x = (rand() * width as f64) as usize;
-                let y = (rand() * height as f64) as usize;
+            if self.rng.rand() < 0.01 || placed.is_empty() {
+                let x = (self.rng.rand() * width as f64) as usize;
+                let y = (self.rng.rand() * height as f64) as usize;
                 self.blocks[x][y] = block::GROUND.clone();
-                placed.push((x, y, random_dir()));
+                placed.push((x, y, random_dir(&mut self.rng)));
             } else {
-                let idx = (rand() * placed.len() as f64) as usize;
+                let idx = (self.rng.rand() * placed.len() as f64) as usize;
                 let (x, y, mut dir) = placed[idx];
 
-                if rand() < 0.05 {
-                    dir = random_dir();
+                if self.rng.rand() < 0.05 {
+                    dir = random_dir(&mut self.rng);
                 }
 
                 let dirv = dir.to_vec();
@@ -383,25 +548,25 @@ impl World {
             }
         }
 
-        let idx = (rand() * placed.len() as f64) as usize;
+        let idx = (self.rng.rand() * placed.len() as f64) as usize;
         let (x, y, _) = placed[idx];
         placed.remove(idx);
         self.add_entity(
             EntityWrapper::WPlayer(
-                Player::new((x as u16, y as u16), self.difficulty.get_start_health())
+                Player::new((x as u16, y as u16), self.difficulty.start_health)
                 )
             );
 
-        let idx = (rand() * placed.len() as f64) as usize;
+        let idx = (self.rng.rand() * placed.len() as f64) as usize;
         let (x, y, _) = placed[idx];
         placed.remove(idx);
         self.add_entity(
             EntityWrapper::WJosef(
                 Josef::new(
                     (x as u16, y as u16),
-                    self.difficulty.get_josef_police_rate(),
-                    self.difficulty.get_josef_speed(),
-                    self.difficulty.get_josef_health()
+                    self.difficulty.josef_police_rate,
+                    self.difficulty.josef_speed,
+                    self.difficulty.josef_health
                     )
             ));
 
@@ -410,91 +575,61 @@ impl World {
 
     pub fn add_entity(&mut self, entity: EntityWrapper) {
         loop {
-            let key = (rand() * <u64>::max_value() as f64) as u64;
+            let key = self.rng.next_u64();
             if !self.entities.contains_key(&key) {
-                self.entities.insert(key, entity);
+                self.insert_entity(key, entity);
                 break;
             }
         }
     }
 
-    // Find a path using a cost and heuristics function.
-    // Cost: Takes a block and returns the cost of passing that block. If None is returned, the
-    // block is considered not passable. Positive here is considered bad.
-    // Heuristics: Takes a world and position and gives back a heuristics of going to that
-    // position. If None, that position is return. Positive here is considered good.
-    pub fn find_path(
-        &self,
-        from: (u16, u16),
-        cost: impl Fn(block::Block, (u16, u16)) -> Option<i32>,
-        heuristics: impl Fn((u16, u16)) -> Option<i32>,
-        steps: u16,
-        ) ->
-        Vec<MoveDir>
-    {
-
-        let mut paths: Vec<((i32, i32), Vec<MoveDir>, (u16, u16))> = vec![((0, 0), vec![], from)];
-        let mut visited: Vec<(u16, u16)> = vec![];
-        let mut best_path: Option<((i32, i32), (Vec<MoveDir>, (u16, u16)))> = None;
-
-        for _ in 0..steps {
-            if paths.len() == 0 {
-                break;
-            }
-
-            if let Some(((last_cost, _), from, pos)) = paths.pop() {
-                for direction in &DIRECTIONS {
-                    let new_pos = direction.move_vec(pos);
-
-                    if visited.contains(&new_pos) {
-                        continue;
-                    }
-                    visited.push(new_pos);
-
-                    // Check for entities
-                    if self.entities.values().any(|en| en.get_pos() == new_pos) {
-                        continue;
-                    }
-
-                    if let Some(block) = self.blocks.get(pos.0 as usize).and_then(|c| c.get(pos.1 as usize)) {
-                        if let Some(cost) = cost(block.clone(), new_pos) {
-                            let new_cost = last_cost + cost;
-                            let mut new_from = from.clone();
-                            new_from.push(*direction);
-
-                            // Check cost
-
-                            let heur =
-                                if let Some(heur) = heuristics(new_pos) { heur }
-                                else { return new_from; };
+    /// Add an entity with a specific id, replacing anything that had it
+    pub fn insert_entity(&mut self, id: u64, entity: EntityWrapper) {
+        self.remove_entity(id);
+        self.positions.insert(id, entity.get_pos());
+        self.entities.insert(id, entity);
+    }
 
+    pub fn remove_entity(&mut self, id: u64) -> Option<EntityWrapper> {
+        let entity = self.entities.remove(&id);
+        if let Some(ref en) = entity {
+            self.positions.remove(id, en.get_pos());
+        }
+        entity
+    }
 
-                            let total_cost = heur - new_cost;
+    pub fn set_entity_pos(&mut self, id: u64, pos: (u16, u16)) {
+        if let Some(en) = self.entities.get_mut(&id) {
+            let old = en.get_pos();
+            *en.get_pos_mut() = pos;
+            self.positions.move_to(id, old, pos);
+        }
+    }
 
-                            for i in 0..paths.len() + 1 {
-                                if paths.get(i).map(|x| (x.0).1).unwrap_or(i32::MAX) > total_cost {
-                                    paths.insert(i, ((new_cost, total_cost), new_from.clone(), new_pos));
-                                    break;
-                                }
-                            }
+    /// Ids of the entities at a position, lowest first
+    pub fn entities_at(&self, pos: (u16, u16)) -> Vec<u64> {
+        self.positions.at(pos)
+    }
 
+    pub fn is_occupied(&self, pos: (u16, u16)) -> bool {
+        self.positions.is_occupied(pos)
+    }
 
-                            let best_cost =
-                                if let Some(((_, best_cost), _)) = best_path {
-                                    best_cost
-                                } else {
-                                    0
-                                };
+    /// If an entity that can't be walked through is at a position. Item drops can be.
+    pub fn is_blocked(&self, pos: (u16, u16)) -> bool {
+        self.is_occupied(pos) && self.entities_at(pos).iter().any(|id| match self.entities.get(id) {
+            Some(EntityWrapper::WItemDrop(_)) | None => false,
+            Some(_) => true,
+        })
+    }
 
-                            if total_cost > best_cost {
-                                best_path = Some(((new_cost, total_cost), (new_from, new_pos)));
-                            }
-                        }
-                    }
-                }
-            }
-        }
+    /// Ids of the entities closer than radius to center, lowest first
+    pub fn entities_within(&self, center: (u16, u16), radius: u16) -> Vec<u64> {
+        self.positions.within(center, radius)
+    }
 
-        best_path.map(|x| (x.1).0).unwrap_or(vec![])
+    /// Ids of the entities with min <= pos <= max, lowest first
+    pub fn entities_in_rect(&self, min: (u16, u16), max: (u16, u16)) -> Vec<u64> {
+        self.positions.in_rect(min, max)
     }
 }
@@ -0,0 +1,85 @@
+// Changing the key bindings
+
+extern crate soviet_sim;
+#[macro_use]
+extern crate lazy_static;
+
+mod common;
+
+use common::*;
+use soviet_sim::controls::{self, Action, Binding};
+use soviet_sim::key::Key;
+
+#[test]
+fn default_bindings() {
+    let defaults = controls::default_bindings();
+    for (action, binding) in &defaults {
+        assert_eq!(controls::conflict(&defaults, *action, binding), None, "{:?} is in the way", action);
+    }
+
+    // Written out and read back over other bindings
+    let mut changed = defaults.clone();
+    changed[0].1 = Binding::new(vec![], Key::letter('W'));
+    assert!(controls::parse_bindings(&changed, &controls::bindings_to_string(&defaults)) == Ok(defaults.clone()));
+
+    let error = |text: &str| controls::parse_bindings(&defaults, text).unwrap_err().msg;
+    assert_eq!(error("fly_up = Up"), "Unknown action fly_up");
+    assert_eq!(error("die = Ctrl+Banana"), "Unknown key \"Banana\"");
+    assert_eq!(error("die = X\ndie = Y"), "die is bound twice");
+    assert_eq!(error("die = M"), "die and super_mine_up get in each other's way");
+    assert_eq!(controls::parse_bindings(&defaults, "# Mine\n\ndie = Up").unwrap_err().line, 3);
+}
+
+// Everything else is in one test since the bindings are shared
+#[test]
+fn rebinding_keys() {
+    let game = TestGame::new();
+    game.press(letter('k'));
+    game.tick();
+    assert!(game.screen_contains("CONTROLS"));
+
+    let die = controls::bindings().iter().position(|(action, _)| *action == Action::Die).unwrap();
+    for _ in 0..die {
+        game.press(DOWN);
+    }
+    game.press(ENTER);
+    game.press(letter('x'));
+    game.tick();
+    assert!(game.screen_contains("Die is now X"));
+
+    // Keys that are in the way of others are refused
+    game.press(ENTER);
+    game.press(letter('m'));
+    game.tick();
+    assert!(game.screen_contains("M would get in the way of Super mine up"));
+    assert_eq!(controls::binding(Action::Die), Some(Binding::new(vec![], Key::letter('X'))));
+
+    // Keys still held when one is let go of become modifiers
+    game.press(ENTER);
+    game.press_with(SHIFT, letter('z'));
+    assert_eq!(controls::binding(Action::Die), Some(Binding::new(vec![Key::Shift], Key::letter('Z'))));
+    assert!(controls::bindings_to_string(&controls::bindings()).contains("die = Shift+Z\n"));
+
+    // The menu shows the new keys
+    game.press(letter('k'));
+    game.tick();
+    assert!(game.screen_contains("Shift+Z:"));
+
+    game.type_number(5);
+    game.press(ENTER);
+    game.tick();
+    game.press(letter('r'));
+    game.tick();
+    assert!(game.is_playing());
+    game.press_with(SHIFT, letter('z'));
+    game.press_with(SHIFT, letter('z'));
+    game.tick();
+    assert!(!game.is_playing());
+
+    // Replays use the bindings they were recorded with
+    controls::reset_bindings();
+    let len = soviet_sim::export_recording();
+    assert!(soviet_sim::start_replay(len));
+    game.ticks(5);
+    assert!(!game.is_playing());
+}
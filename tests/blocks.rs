@@ -0,0 +1,91 @@
+// Blocks loaded from definition files
+
+extern crate soviet_sim;
+#[macro_use]
+extern crate lazy_static;
+
+mod common;
+
+use common::*;
+use soviet_sim::block::{self, WalkEffect};
+
+const CUSTOM: &str = r#"
+# Lava hurts, conveyors push
+[lava]
+name = "Lava"
+char = "~"
+fg = [255, 100, 0]
+passable = true
+walk = "damage"
+amount = 3
+
+[conveyor]
+name = "Conveyor"
+char = ">"
+fg = [200, 200, 200]
+passable = true
+walk = "push"
+direction = "right"
+"#;
+
+#[test]
+fn builtin_blocks() {
+    assert!(block::by_id("ground").unwrap().is_passable());
+    assert!(block::by_id("wall").unwrap().is_breakable());
+    assert_eq!(block::by_id("mover").unwrap().get_walk(), WalkEffect::Teleport);
+    assert!(block::by_name(&block::STONE.name).unwrap() == *block::STONE);
+    assert!(block::by_id("nope").is_none());
+}
+
+#[test]
+fn walking_onto_custom_blocks() {
+    let game = TestGame::playing(3);
+    assert_eq!(block::load_blocks(CUSTOM), Ok(2));
+    let lava = block::by_id("lava").unwrap();
+    let conveyor = block::by_id("conveyor").unwrap();
+
+    let (x, y) = game.player_pos();
+    let hunger = game.player(|player| player.hunger);
+    game.world(|world| {
+        world.blocks[x as usize + 1][y as usize] = conveyor.clone();
+        world.blocks[x as usize + 2][y as usize] = lava.clone();
+        world.blocks[x as usize + 3][y as usize] = block::GROUND.clone();
+    });
+
+    // Pushed one step further, but only once even though it lands on lava
+    game.press(RIGHT);
+    assert_eq!(game.player_pos(), (x + 2, y));
+    assert_eq!(game.player(|player| player.hunger), hunger);
+
+    // Off and back on again
+    game.press(RIGHT);
+    game.press(LEFT);
+    assert_eq!(game.player_pos(), (x + 2, y));
+    assert_eq!(game.player(|player| player.hunger), hunger - 3);
+}
+
+#[test]
+fn broken_definitions() {
+    let error = |text: &str| block::load_blocks(text).unwrap_err().msg;
+
+    assert_eq!(
+        error("[a]\nname = \"A\"\nchar = \"a\"\nfg = [1, 2, 3]\nsolid = true"),
+        "Unknown key solid in [a]");
+    assert_eq!(
+        error("[a]\nname = \"A\"\nchar = \"ab\"\nfg = [1, 2, 3]"),
+        "char in [a] should be a single character");
+    assert_eq!(
+        error("[a]\nname = \"A\"\nchar = \"a\"\nfg = [1, 2, 3]\nwalk = \"fly\""),
+        "Unknown walk effect fly in [a]");
+    assert_eq!(
+        error("[a]\nchar = \"a\"\nfg = [1, 2, 3]"),
+        "[a] is missing name");
+    assert_eq!(
+        error("[a]\nname = \"A\"\nchar = \"a\"\nfg = [1, 2]"),
+        "fg in [a] should be a list of three numbers 0-255");
+    assert_eq!(block::load_blocks("[a]\nname = \"A\"\nchar = \"a\"\nfg = 3").unwrap_err().line, 4);
+
+    // Nothing from a broken file is added, not even the good parts
+    assert!(block::load_blocks("[good]\nname = \"Good\"\nchar = \"g\"\nfg = [1, 2, 3]\n[bad]").is_err());
+    assert!(block::by_id("good").is_none());
+}
@@ -0,0 +1,136 @@
+// Helpers for driving the game like the page does, against an in-memory host
+
+#![allow(dead_code)]
+
+use soviet_sim;
+use soviet_sim::host::{MemoryHost, set_host};
+use soviet_sim::world::World;
+use soviet_sim::entity::{EntityWrapper, Player};
+use soviet_sim::inventory::InventoryItem;
+
+use std::sync::{Mutex, MutexGuard};
+
+pub const SIZE: (u16, u16) = (100, 50);
+
+// JavaScript keyCodes
+pub const ENTER: u8 = 13;
+pub const SHIFT: u8 = 16;
+pub const CTRL: u8 = 17;
+pub const ESCAPE: u8 = 27;
+pub const LEFT: u8 = 37;
+pub const UP: u8 = 38;
+pub const RIGHT: u8 = 39;
+pub const DOWN: u8 = 40;
+
+pub fn letter(ch: char) -> u8 {
+    ch.to_ascii_uppercase() as u8
+}
+
+lazy_static! {
+    // The game is one global, so only one test can play at a time
+    static ref GAME_LOCK: Mutex<()> = Mutex::new(());
+}
+
+pub struct TestGame {
+    pub host: MemoryHost,
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl TestGame {
+    /// Start at the menu
+    pub fn new() -> TestGame {
+        let lock = GAME_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+        let host = MemoryHost::new();
+        set_host(Box::new(host.clone()));
+        soviet_sim::start(SIZE.0, SIZE.1);
+        soviet_sim::resize(SIZE.0, SIZE.1);
+
+        TestGame { host: host, _lock: lock }
+    }
+
+    /// Start a run on the default difficulty with a given seed
+    pub fn playing(seed: u32) -> TestGame {
+        let game = TestGame::new();
+        game.type_number(seed);
+        game.press(ENTER);
+        game.tick();
+        game
+    }
+
+    pub fn tick(&self) {
+        soviet_sim::tick();
+    }
+
+    pub fn ticks(&self, n: usize) {
+        for _ in 0..n {
+            soviet_sim::tick();
+        }
+    }
+
+    pub fn press(&self, key: u8) {
+        soviet_sim::key_down(key);
+        soviet_sim::key_up(key);
+    }
+
+    /// Press a key while holding another one
+    pub fn press_with(&self, modifier: u8, key: u8) {
+        soviet_sim::key_down(modifier);
+        self.press(key);
+        soviet_sim::key_up(modifier);
+    }
+
+    pub fn type_number(&self, n: u32) {
+        for ch in n.to_string().chars() {
+            self.press(ch as u8);
+        }
+    }
+
+    /// Everything on the screen, one line per row
+    pub fn screen(&self) -> String {
+        (0..SIZE.1).map(|y| self.host.row(y)).collect::<Vec<_>>().join("\n")
+    }
+
+    pub fn screen_contains(&self, text: &str) -> bool {
+        self.screen().contains(text)
+    }
+
+    /// The colour of the first place some text is on the screen
+    pub fn text_color(&self, text: &str) -> Option<(u8, u8, u8)> {
+        let screen = self.screen();
+        let (y, line) = screen.lines().enumerate().find(|(_, line)| line.contains(text))?;
+        let x = line[..line.find(text)?].chars().count();
+        let state = self.host.state.lock().unwrap();
+        Some(state.screen[x][y].col)
+    }
+
+    pub fn world<T>(&self, f: impl FnOnce(&mut World) -> T) -> T {
+        soviet_sim::with_world(f).expect("Not playing")
+    }
+
+    pub fn player<T>(&self, f: impl FnOnce(&mut Player) -> T) -> T {
+        self.world(|world| {
+            let id = world.get_player_id().expect("No player");
+            match world.entities.get_mut(&id) {
+                Some(EntityWrapper::WPlayer(player)) => f(player),
+                _ => unreachable!(),
+            }
+        })
+    }
+
+    pub fn player_pos(&self) -> (u16, u16) {
+        self.player(|player| player.pos)
+    }
+
+    /// Where the items on the ground are, what they are and how many
+    pub fn drops(&self) -> Vec<((u16, u16), InventoryItem, u64)> {
+        self.world(|world|
+            world.entities.values()
+                .filter_map(|en| if let EntityWrapper::WItemDrop(drop) = en { Some((drop.pos, drop.item.clone(), drop.count)) } else { None })
+                .collect())
+    }
+
+    pub fn is_playing(&self) -> bool {
+        soviet_sim::with_world(|_| ()).is_some()
+    }
+}
@@ -0,0 +1,107 @@
+// Difficulty presets, custom difficulties and their codes
+
+extern crate soviet_sim;
+#[macro_use]
+extern crate lazy_static;
+
+mod common;
+
+use common::*;
+use soviet_sim::save;
+use soviet_sim::controls;
+use soviet_sim::key::Key;
+use soviet_sim::difficulty::{Difficulty, Preset, Param, CodeError};
+
+use std::sync::mpsc::channel;
+
+const MINUS: u8 = 189;
+
+fn type_code(game: &TestGame, code: &str) {
+    for part in code.split('-').enumerate() {
+        if part.0 > 0 {
+            game.press(MINUS);
+        }
+        game.type_number(part.1.parse().unwrap());
+    }
+}
+
+#[test]
+fn codes() {
+    assert_eq!(Difficulty::EASY.code(), "3-60-1000-20-7-5-5");
+    assert_eq!(Difficulty::from_code("3-60-1000-20-7-5-5"), Ok(Difficulty::EASY.custom()));
+
+    let mut custom = Difficulty::REALITY;
+    custom.set(Param::StartHealth, 40);
+    assert_eq!(custom.preset, Preset::Custom);
+    assert_eq!(Difficulty::from_code(&custom.code()), Ok(custom));
+
+    assert_eq!(Difficulty::from_code("1-2-3"), Err(CodeError::WrongLength(3)));
+    assert_eq!(Difficulty::from_code("3-60-lots-20-7-5-5"), Err(CodeError::NotANumber("lots".to_string())));
+    assert_eq!(Difficulty::from_code("0-60-1000-20-7-5-5"), Err(CodeError::OutOfRange(Param::JosefHealth, 0)));
+
+    assert_eq!(Difficulty::EASY.harder(), Difficulty::MEDIUM);
+    assert_eq!(Difficulty::REALITY.harder(), Difficulty::REALITY);
+    assert_eq!(custom.harder(), custom);
+}
+
+#[test]
+fn custom_difficulty_editor() {
+    let game = TestGame::new();
+    // The terminal holds modifier letters until the next key, so the editor's key can't be one
+    assert!(!controls::is_modifier(&Key::letter('D')));
+    game.press(letter('d'));
+    game.tick();
+    assert!(game.screen_contains("CUSTOM DIFFICULTY"));
+    assert!(game.screen_contains("Code: 3-60-1000-20-7-5-5"));
+
+    // One more health for Josef
+    game.press(RIGHT);
+    game.press(ESCAPE);
+    game.tick();
+    assert!(game.screen_contains("Custom (4-60-1000-20-7-5-5)"));
+
+    // It comes after the presets
+    game.press(LEFT);
+    game.tick();
+    assert!(game.screen_contains("1944 (Reality)"));
+    game.press(RIGHT);
+    game.press(ENTER);
+    game.tick();
+    assert_eq!(game.world(|world| world.difficulty.josef_health), 4);
+
+    game.world(|world| {
+        let loaded = save::load_world(&save::save_world(world), channel().0).expect("Can't load");
+        assert_eq!(loaded.difficulty, world.difficulty);
+    });
+
+    let len = soviet_sim::export_recording();
+    assert!(soviet_sim::start_replay(len));
+    assert_eq!(game.world(|world| world.difficulty.code()), "4-60-1000-20-7-5-5");
+}
+
+#[test]
+fn typing_a_code() {
+    let game = TestGame::new();
+    game.press(letter('d'));
+    type_code(&game, "17-15-50-7-1-1-9");
+    game.tick();
+    assert!(game.screen_contains("Code: 17-15-50-7-1-1-9_"));
+    game.press(ENTER);
+    game.tick();
+    assert!(game.screen_contains("Code loaded"));
+    assert!(game.screen_contains("Code: 17-15-50-7-1-1-9 "));
+
+    type_code(&game, "1-2");
+    game.press(ENTER);
+    game.tick();
+    assert!(game.screen_contains("A code has 7 numbers, not 2"));
+
+    // Kept for the next time the menu is opened
+    game.press(ENTER);
+    soviet_sim::start(SIZE.0, SIZE.1);
+    for _ in 0..5 {
+        game.press(RIGHT);
+    }
+    game.tick();
+    assert!(game.screen_contains("Custom (17-15-50-7-1-1-9)"));
+}
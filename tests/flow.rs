@@ -0,0 +1,54 @@
+// The police flow field, patched block by block vs built from scratch
+
+extern crate soviet_sim;
+
+use soviet_sim::flow::{FlowField, UNREACHABLE};
+use soviet_sim::block;
+use soviet_sim::rng::Rng;
+
+fn distances(field: &FlowField, size: (u16, u16)) -> Vec<u32> {
+    (0..size.0).flat_map(|x| (0..size.1).map(move |y| (x, y))).map(|pos| field.distance(pos)).collect()
+}
+
+#[test]
+fn walls_around_the_target() {
+    let mut blocks = vec![vec![block::GROUND.clone(); 5]; 5];
+    let mut field = FlowField::new();
+    field.update(&blocks, (2, 2));
+    assert_eq!(field.distance((0, 0)), 4);
+
+    for &pos in &[(1, 2), (3, 2), (2, 1), (2, 3)] {
+        blocks[pos.0][pos.1] = block::WALL.clone();
+        field.block_changed(&blocks, (pos.0 as u16, pos.1 as u16));
+    }
+    assert_eq!(field.distance((0, 0)), UNREACHABLE);
+    assert_eq!(field.distance((2, 2)), 0);
+
+    blocks[2][3] = block::GROUND.clone();
+    field.block_changed(&blocks, (2, 3));
+    assert_eq!(field.distance((0, 0)), 6);
+}
+
+#[test]
+fn patching_matches_rebuilding() {
+    let size = (30, 20);
+    let mut rng = Rng::new(5);
+    let mut blocks = vec![vec![block::GROUND.clone(); size.1 as usize]; size.0 as usize];
+
+    let mut field = FlowField::new();
+    field.update(&blocks, (15, 10));
+
+    for _ in 0..500 {
+        let pos = ((rng.rand() * size.0 as f64) as u16, (rng.rand() * size.1 as f64) as u16);
+        if pos == (15, 10) {
+            continue;
+        }
+        blocks[pos.0 as usize][pos.1 as usize] =
+            if rng.rand() < 0.6 { block::WALL.clone() } else { block::GROUND.clone() };
+        field.block_changed(&blocks, pos);
+
+        let mut fresh = FlowField::new();
+        fresh.update(&blocks, (15, 10));
+        assert_eq!(distances(&field, size), distances(&fresh, size));
+    }
+}
@@ -0,0 +1,198 @@
+// The player's inventory: slots, stacks and items on the ground
+
+extern crate soviet_sim;
+#[macro_use]
+extern crate lazy_static;
+
+mod common;
+
+use common::*;
+use soviet_sim::block;
+use soviet_sim::save;
+use soviet_sim::entity::{EntityWrapper, ItemDrop, Police, DESPAWN_TIME};
+use soviet_sim::inventory::{Inventory, InventoryItem, PICKAXE};
+
+use std::sync::mpsc::channel;
+
+fn stone() -> InventoryItem {
+    InventoryItem::Block(block::STONE.clone())
+}
+
+#[test]
+fn stacks_and_slots() {
+    let mut inventory = Inventory::new(3);
+
+    // Stacks fill up before new slots are used
+    assert_eq!(inventory.add(stone(), 150), 0);
+    assert_eq!(inventory.add(stone(), 40), 0);
+    assert_eq!(inventory.slots().to_vec(), vec![(stone(), 99), (stone(), 91)]);
+
+    // Tools each get their own slot, and things that don't fit are left over
+    assert_eq!(inventory.add(PICKAXE, 2), 1);
+    assert_eq!(inventory.add(InventoryItem::Bullet, 1), 1);
+    assert_eq!(inventory.count(&stone()), 190);
+
+    // Taking empties the last stacks first
+    assert_eq!(inventory.take(&stone(), 100), 100);
+    assert_eq!(inventory.slots().to_vec(), vec![(stone(), 90), (PICKAXE, 1)]);
+    assert_eq!(inventory.take(&InventoryItem::Bomb, 1), 0);
+}
+
+#[test]
+fn using_up_a_stack_before_the_active_one() {
+    let mut inventory = Inventory::with_items(5, vec![(stone(), 2), (InventoryItem::Bullet, 4), (InventoryItem::Bomb, 1)]);
+    inventory.select(2);
+    assert_eq!(inventory.take(&stone(), 2), 2);
+    assert_eq!(inventory.active_slot(), Some(&(InventoryItem::Bomb, 1)));
+}
+
+#[test]
+fn tools_wear_on_their_own() {
+    let mut inventory = Inventory::new(5);
+    inventory.add(InventoryItem::Pickaxe(2, 10), 1);
+    inventory.add(InventoryItem::Pickaxe(7, 10), 1);
+
+    assert!(inventory.wear("pickaxe"));
+    assert_eq!(inventory.slots().to_vec(), vec![(InventoryItem::Pickaxe(1, 10), 1), (InventoryItem::Pickaxe(7, 10), 1)]);
+
+    // The first one breaks, the other is untouched
+    assert!(inventory.wear("pickaxe"));
+    assert_eq!(inventory.slots().to_vec(), vec![(InventoryItem::Pickaxe(7, 10), 1)]);
+    assert!(!inventory.wear("super_boots"));
+}
+
+#[test]
+fn worn_tools_count_as_the_tool() {
+    let mut inventory = Inventory::with_items(5, vec![(InventoryItem::Pickaxe(2, 10), 1), (stone(), 3)]);
+    assert_eq!(inventory.count(&PICKAXE), 1);
+    assert_eq!(inventory.take(&PICKAXE, 1), 1);
+    assert_eq!(inventory.slots().to_vec(), vec![(stone(), 3)]);
+}
+
+#[test]
+fn dropping_and_picking_up() {
+    let game = TestGame::playing(8);
+    let (x, y) = game.player_pos();
+    game.world(|world| {
+        world.blocks[x as usize + 1][y as usize] = block::GROUND.clone();
+        world.blocks[x as usize + 2][y as usize] = block::GROUND.clone();
+    });
+    game.player(|player| player.inventory = Inventory::with_items(5, vec![(stone(), 30), (InventoryItem::Bullet, 4)]));
+
+    game.press_with(letter('q'), RIGHT);
+    assert_eq!(game.drops(), vec![((x + 1, y), stone(), 30)]);
+    assert_eq!(game.player(|player| player.inventory.count(&stone())), 0);
+
+    // Still there after saving
+    game.world(|world| {
+        let loaded = save::load_world(&save::save_world(world), channel().0).expect("Can't load");
+        assert_eq!(loaded.entities.values().filter(|en| en.get_name() == "Stone x30").count(), 1);
+    });
+
+    // Walking onto it picks it up, as much as fits
+    game.player(|player| player.inventory = Inventory::with_items(1, vec![(stone(), 80)]));
+    game.press(RIGHT);
+    assert_eq!(game.player_pos(), (x + 1, y));
+    assert_eq!(game.player(|player| player.inventory.count(&stone())), 99);
+    assert_eq!(game.drops(), vec![((x + 1, y), stone(), 11)]);
+
+    game.player(|player| player.inventory = Inventory::new(5));
+    game.press(RIGHT);
+    game.press(LEFT);
+    assert_eq!(game.player(|player| player.inventory.count(&stone())), 11);
+    assert!(game.drops().is_empty());
+}
+
+#[test]
+fn dropping_onto_a_pile() {
+    let game = TestGame::playing(8);
+    let (x, y) = game.player_pos();
+    game.world(|world| {
+        world.blocks[x as usize + 1][y as usize] = block::GROUND.clone();
+        world.blocks[x as usize - 1][y as usize] = block::GROUND.clone();
+        world.add_entity(EntityWrapper::WPolice(Police::new((x - 1, y), 20, 7)));
+    });
+    game.player(|player| player.inventory = Inventory::with_items(5, vec![(stone(), 99), (stone(), 10)]));
+
+    game.press_with(letter('q'), RIGHT);
+    game.press_with(letter('q'), RIGHT);
+    assert_eq!(game.drops(), vec![((x + 1, y), stone(), 109)]);
+
+    // Not onto the police
+    game.player(|player| player.inventory = Inventory::with_items(5, vec![(stone(), 5)]));
+    game.press_with(letter('q'), LEFT);
+    assert_eq!(game.drops(), vec![((x + 1, y), stone(), 109)]);
+    assert_eq!(game.player(|player| player.inventory.count(&stone())), 5);
+}
+
+#[test]
+fn items_on_the_ground_despawn() {
+    let game = TestGame::playing(8);
+    let (x, y) = game.player_pos();
+    let mut old = ItemDrop::new((x + 3, y), stone(), 5);
+    old.age = DESPAWN_TIME - 2;
+    game.world(|world| world.add_entity(EntityWrapper::WItemDrop(old)));
+
+    game.tick();
+    assert_eq!(game.drops(), vec![((x + 3, y), stone(), 5)]);
+    game.tick();
+    assert!(game.drops().is_empty());
+}
+
+#[test]
+fn number_keys_and_using_items() {
+    let game = TestGame::playing(8);
+    let (x, y) = game.player_pos();
+    game.world(|world| for dx in 1..6 {
+        world.blocks[(x + dx) as usize][y as usize] = block::GROUND.clone();
+    });
+    game.world(|world| world.blocks[(x + 6) as usize][y as usize] = block::WALL.clone());
+    game.player(|player| player.inventory = Inventory::with_items(5, vec![(stone(), 3), (InventoryItem::Bomb, 2), (PICKAXE, 1)]));
+
+    // Slots that are empty can't be picked
+    game.press(b'3');
+    assert_eq!(game.player(|player| player.inventory.active), 2);
+    game.press(b'5');
+    assert_eq!(game.player(|player| player.inventory.active), 2);
+
+    // Tools are switched off and on, and aren't worn while off
+    game.press_with(letter('e'), RIGHT);
+    assert!(!game.player(|player| player.inventory.tool_on("pickaxe")));
+    assert!(!game.player(|player| player.inventory.wear("pickaxe")));
+    game.world(|world| {
+        let loaded = save::load_world(&save::save_world(world), channel().0).expect("Can't load");
+        let id = loaded.get_player_id().unwrap();
+        match loaded.entities[&id] {
+            EntityWrapper::WPlayer(ref player) => assert_eq!(player.inventory.tools_off().to_vec(), vec!["pickaxe".to_string()]),
+            _ => panic!("Not a player"),
+        }
+    });
+    game.press_with(letter('e'), RIGHT);
+    assert!(game.player(|player| player.inventory.tool_on("pickaxe")));
+
+    // Bombs are thrown, stopping before the wall
+    game.press(b'2');
+    game.press_with(letter('e'), RIGHT);
+    let bombs: Vec<(u16, u16)> = game.world(|world|
+        world.entities.values()
+            .filter_map(|en| if let EntityWrapper::WBomb(_) = en { Some(en.get_pos()) } else { None })
+            .collect());
+    assert_eq!(bombs, vec![(x + 4, y)]);
+    assert_eq!(game.player(|player| player.inventory.count(&InventoryItem::Bomb)), 1);
+
+    // Blocks are placed like with P
+    game.press(b'1');
+    game.press_with(letter('e'), RIGHT);
+    assert!(game.world(|world| world.blocks[(x + 1) as usize][y as usize] == *block::STONE));
+}
+
+#[test]
+fn mining_with_a_full_inventory() {
+    let game = TestGame::playing(9);
+    let (x, y) = game.player_pos();
+    game.world(|world| world.blocks[x as usize + 1][y as usize] = block::WALL.clone());
+    game.player(|player| player.inventory = Inventory::with_items(1, vec![(stone(), 1)]));
+
+    game.press_with(letter('m'), RIGHT);
+    assert_eq!(game.drops(), vec![((x + 1, y), InventoryItem::Block(block::WALL.clone()), 1)]);
+}
@@ -0,0 +1,51 @@
+// Turning key codes from the host into keys
+
+extern crate soviet_sim;
+
+use soviet_sim::key::{self, Key};
+use soviet_sim::move_dir::MoveDir;
+
+#[test]
+fn key_codes() {
+    // The same place on the keyboard whatever is printed on it
+    assert_eq!(key::parse_code("KeyW"), Some(Key::letter('W')));
+    assert_eq!(key::parse_code("Digit1"), Some(Key::Digit(1)));
+    assert_eq!(key::parse_code("Numpad5"), Some(Key::Numpad(5)));
+    assert_eq!(key::parse_code("F5"), Some(Key::F(5)));
+    assert_eq!(key::parse_code("Escape"), Some(Key::Escape));
+    assert_eq!(key::parse_code("Space"), Some(Key::Space));
+    assert_eq!(key::parse_code("Equal"), Some(Key::Equals));
+    assert_eq!(key::parse_code("ArrowLeft"), Some(Key::Arrow(MoveDir::Left)));
+    assert_eq!(key::parse_code("ControlRight"), Some(Key::Ctrl));
+    assert_eq!(key::parse_code("F13"), None);
+    assert_eq!(key::parse_code("Keyw"), None);
+    assert_eq!(key::parse_code("Numpad10"), None);
+
+    // Browsers don't agree on some of these
+    assert_eq!(key::parse_key(27), Some(Key::Escape));
+    assert_eq!(key::parse_key(32), Some(Key::Space));
+    assert_eq!(key::parse_key(189), Some(Key::Minus));
+    assert_eq!(key::parse_key(173), Some(Key::Minus));
+    assert_eq!(key::parse_key(99), Some(Key::Numpad(3)));
+    assert_eq!(key::parse_key(123), Some(Key::F(12)));
+    assert_eq!(key::parse_key(219), Some(Key::BracketLeft));
+    assert_eq!(key::parse_key(255), None);
+}
+
+#[test]
+fn key_names() {
+    assert_eq!(Key::Escape.to_string(), "Esc");
+    assert_eq!(Key::Numpad(7).to_string(), "Num7");
+    assert_eq!(Key::F(11).to_string(), "F11");
+    assert_eq!(Key::Semicolon.to_string(), ";");
+
+    let keys = vec![
+        Key::Arrow(MoveDir::Up), Key::letter('q'), Key::Digit(0), Key::F(1), Key::F(12), Key::Numpad(9),
+        Key::Shift, Key::Alt, Key::Ctrl, Key::Meta, Key::Enter, Key::Backspace, Key::Tab, Key::Escape,
+        Key::Space, Key::Plus, Key::Minus, Key::Equals, Key::Comma, Key::Period, Key::Slash,
+        Key::Backslash, Key::Semicolon, Key::Quote, Key::Backquote, Key::BracketLeft, Key::BracketRight,
+    ];
+    for key in keys {
+        assert_eq!(key::parse_name(&key.name()), Some(key.clone()), "{} is saved as {}", key, key.name());
+    }
+}
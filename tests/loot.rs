@@ -0,0 +1,53 @@
+// What enemies drop when they die
+
+extern crate soviet_sim;
+#[macro_use]
+extern crate lazy_static;
+
+mod common;
+
+use common::*;
+use soviet_sim::block;
+use soviet_sim::loot;
+use soviet_sim::entity::{EntityWrapper, Police};
+use soviet_sim::inventory::InventoryItem;
+
+// Everything is in one test since the loot tables are shared by all of them
+#[test]
+fn police_drop_loot() {
+    let police = loot::table("police").expect("Police have no loot");
+    assert!(police.drops.iter().any(|drop| drop.item == InventoryItem::Bullet && drop.chance == 50));
+
+    let error = |text: &str| loot::load_loot(text).unwrap_err().msg;
+    assert_eq!(
+        error("[josef]\ndrops = [[\"bomb\", 1, 100]]"),
+        "Unknown enemy josef, it should be one of police");
+    assert_eq!(
+        error("[police]\ndrops = [[\"gold\", 1, 100]]"),
+        "Unknown item gold");
+    assert_eq!(
+        error("[police]\ndrops = [[\"bomb\", 1, 120]]"),
+        "The chance of bomb in [police] should be 0-100");
+    assert_eq!(
+        error("[police]\ndrops = [[\"bomb\", 1]]"),
+        "drops in [police] should be a list of [item, amount, chance]");
+    assert_eq!(loot::load_loot("[police]\n\ndrops = [[\"bomb\", 0, 10]]").unwrap_err().line, 3);
+
+    assert_eq!(loot::load_loot("[police]\ndrops = [[\"bomb\", 2, 100], [\"bullet\", 1, 0]]"), Ok(1));
+
+    let game = TestGame::playing(4);
+    let (x, y) = game.player_pos();
+    let pos = (x + 5, y);
+    game.world(|world| {
+        world.blocks[pos.0 as usize][pos.1 as usize] = block::GROUND.clone();
+        world.add_entity(EntityWrapper::WPolice(Police::new(pos, 10, 10)));
+        let id = *world.entities.iter()
+            .find(|(_, en)| en.get_pos() == pos)
+            .expect("No police")
+            .0;
+        let hurt = world.entities[&id].get_hurt_fn();
+        hurt(world, id, 1);
+    });
+
+    assert!(game.drops() == vec![(pos, InventoryItem::Bomb, 2)]);
+}
@@ -0,0 +1,78 @@
+// Path finding on small hand made worlds
+
+extern crate soviet_sim;
+
+use soviet_sim::world::World;
+use soviet_sim::block;
+use soviet_sim::difficulty::Difficulty;
+use soviet_sim::move_dir::MoveDir;
+use soviet_sim::pathfinding::find_path;
+
+use std::collections::HashSet;
+use std::sync::mpsc::channel;
+
+// '#' is a wall, anything else ground
+fn parse_world(rows: &[&str]) -> World {
+    let mut world = World::empty(Difficulty::EASY, 0, channel().0);
+    world.blocks = (0..rows[0].len())
+        .map(|x| rows.iter()
+             .map(|row| if row.as_bytes()[x] == b'#' { block::WALL.clone() } else { block::GROUND.clone() })
+             .collect())
+        .collect();
+    world
+}
+
+fn walk(block: &block::Block, _pos: (u16, u16)) -> Option<u32> {
+    if block.is_passable() { Some(1) } else { None }
+}
+
+fn towards(goal: (u16, u16)) -> impl Fn((u16, u16)) -> Option<u32> {
+    move |(x, y)| {
+        let dist = (x as i32 - goal.0 as i32).abs() + (y as i32 - goal.1 as i32).abs();
+        if dist == 0 { None } else { Some(dist as u32) }
+    }
+}
+
+fn end(from: (u16, u16), path: &[MoveDir]) -> (u16, u16) {
+    path.iter().fold(from, |pos, dir| dir.move_vec(pos))
+}
+
+#[test]
+fn around_a_wall() {
+    let world = parse_world(&[
+        ".....",
+        ".###.",
+        "..#..",
+        ".....",
+    ]);
+    let path = find_path(&world, (1, 2), |_| false, walk, towards((3, 2)), 1000);
+    assert_eq!(end((1, 2), &path), (3, 2));
+    assert_eq!(path.len(), 4);
+}
+
+#[test]
+fn into_an_occupied_goal() {
+    let world = parse_world(&["....."]);
+    let mut occupied = HashSet::new();
+    occupied.insert((2, 0));
+    occupied.insert((4, 0));
+
+    assert_eq!(find_path(&world, (0, 0), |pos| occupied.contains(&pos), walk, towards((2, 0)), 1000).len(), 2);
+    // Can't walk through (2, 0) on the way to (4, 0)
+    assert_eq!(end((0, 0), &find_path(&world, (0, 0), |pos| occupied.contains(&pos), walk, towards((4, 0)), 1000)), (1, 0));
+}
+
+#[test]
+fn closest_when_out_of_budget() {
+    let world = parse_world(&[
+        "...#....",
+        "...#....",
+        "...#....",
+    ]);
+    let path = find_path(&world, (0, 1), |_| false, walk, towards((6, 1)), 1000);
+    assert_eq!(end((0, 1), &path), (2, 1));
+
+    let world = parse_world(&["........"]);
+    let path = find_path(&world, (0, 0), |_| false, walk, towards((7, 0)), 3);
+    assert_eq!(end((0, 0), &path), (3, 0));
+}
@@ -0,0 +1,109 @@
+// Crafting recipes loaded from definition files
+
+extern crate soviet_sim;
+
+use soviet_sim::crafting::{self, Recipe};
+use soviet_sim::inventory::{InventoryItem, PICKAXE};
+use soviet_sim::block;
+
+fn recipe(id: &str) -> Recipe {
+    crafting::recipes().iter().find(|recipe| recipe.id == id).cloned().expect("No such recipe")
+}
+
+// Everything is in one test since the recipes are shared by all of them
+#[test]
+fn loading_recipes() {
+    let ids: Vec<String> = crafting::recipes().iter().map(|recipe| recipe.id.clone()).collect();
+    assert_eq!(ids, vec!["workbench", "forge", "bullet", "bomb", "mover", "super_boots", "pickaxe"]);
+
+    let bomb = recipe("bomb");
+    assert!(bomb.out == vec![(InventoryItem::Bomb, 1)]);
+    assert!(bomb.needed == vec![
+        (InventoryItem::Bullet, 7),
+        (InventoryItem::Block(block::STONE.clone()), 10),
+    ]);
+
+    let error = |text: &str| crafting::load_recipes(text).unwrap_err().msg;
+
+    assert_eq!(
+        error("[x]\nout = \"bomb\"\nneeds = [[\"gold\", 1]]"),
+        "Unknown item gold");
+    assert_eq!(
+        error("[x]\nout = \"block:gold\"\nneeds = [[\"bullet\", 1]]"),
+        "Unknown item block:gold");
+    assert_eq!(
+        error("[x]\nout = \"block:stone\"\nneeds = [[\"bullet\", 0]]"),
+        "needs in [x] has 0 bullet, it should be at least 1");
+    assert_eq!(
+        error("[x]\nout = \"bomb\"\nneeds = [[\"bullet\", 1]]"),
+        "[x] makes bomb, but so does [bomb]");
+    assert_eq!(
+        error("[x]\nout = \"block:stone\"\nneeds = [[\"bullet\", 1], [\"block:stone\", 2]]"),
+        "[x] needs block:stone, which is what it makes");
+    assert_eq!(
+        error("[x]\nout = \"block:stone\"\nneeds = [\"bullet\", 1]"),
+        "needs in [x] should be a list of [item, amount]");
+    assert_eq!(
+        error("[x]\nout = [[\"block:stone\", 2], [\"bomb\", 1]]\nneeds = [[\"bullet\", 1]]"),
+        "[x] makes bomb, but so does [bomb]");
+    assert_eq!(
+        error("[x]\nout = \"block:stone\"\nneeds = [[\"bullet\", 1]]\ntools = [\"block:stone\"]"),
+        "[x] needs block:stone, which is what it makes");
+    assert_eq!(
+        error("[x]\nout = \"block:stone\"\nneeds = [[\"bullet\", 1]]\nstation = \"smelter\""),
+        "Unknown block smelter");
+    assert_eq!(
+        error("[x]\nout = \"block:stone\"\ntools = [\"pickaxe\"]"),
+        "[x] is missing needs");
+    assert_eq!(
+        error("[x]\nout = \"block:stone\"\nneeds = []"),
+        "needs in [x] is empty");
+    assert_eq!(
+        error("[x]\nout = \"block:wall\"\nneeds = [[\"bullet\", 1]]"),
+        "[x] needs [bullet] needs [x], which goes in a circle");
+    assert_eq!(crafting::load_recipes("[x]\nout = \"bomb\"\n\nneeds = [\n  [\"bullet\", 0],\n]").unwrap_err().line, 4);
+    assert_eq!(crafting::recipes().len(), 7);
+
+    // Cheaper pickaxes, written over several lines
+    let cheap = r#"
+        [pickaxe]
+        out = "pickaxe"
+        needs = [
+            ["block:wall", 3],  # Just some walls
+            ["bullet", 1],
+        ]
+    "#;
+    assert_eq!(crafting::load_recipes(cheap), Ok(1));
+    let pickaxe = recipe("pickaxe");
+    assert!(pickaxe.out == vec![(PICKAXE, 1)]);
+    assert!(pickaxe.needed == vec![
+        (InventoryItem::Block(block::WALL.clone()), 3),
+        (InventoryItem::Bullet, 1),
+    ]);
+    assert_eq!(crafting::recipes().len(), 7);
+
+    // Swapping what two recipes make is fine when both are in the same file
+    let swapped = r#"
+        [bomb]
+        out = "bullet"
+        needs = [["block:stone", 1]]
+        [bullet]
+        out = "bomb"
+        needs = [["block:stone", 2]]
+    "#;
+    assert_eq!(crafting::load_recipes(swapped), Ok(2));
+    assert!(recipe("bomb").out == vec![(InventoryItem::Bullet, 1)]);
+
+    let fancy = r#"
+        [rubble]
+        out = [["block:stone", 4], ["block:ground", 1]]
+        needs = [["block:wall", 1]]
+        tools = ["pickaxe"]
+        station = "mover"
+    "#;
+    assert_eq!(crafting::load_recipes(fancy), Ok(1));
+    let rubble = recipe("rubble");
+    assert_eq!(rubble.get_name(), "Stone x4, Ground");
+    assert!(rubble.tools == vec![PICKAXE]);
+    assert!(rubble.station == Some(block::MOVER.clone()));
+}
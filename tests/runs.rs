@@ -0,0 +1,254 @@
+// Whole runs: saving them, replaying them and simulating them
+
+extern crate soviet_sim;
+#[macro_use]
+extern crate lazy_static;
+
+mod common;
+
+use common::*;
+use soviet_sim::save;
+use soviet_sim::block;
+use soviet_sim::entity::EntityWrapper;
+use soviet_sim::inventory::{Inventory, InventoryItem, PLAYER_SLOTS};
+use soviet_sim::sim::Simulation;
+use soviet_sim::difficulty::Difficulty;
+use soviet_sim::highscores::{self, Score};
+use soviet_sim::controls::Action;
+use soviet_sim::move_dir::MoveDir;
+
+use std::sync::mpsc::channel;
+
+fn entity_positions(game: &TestGame) -> Vec<(u64, (u16, u16))> {
+    game.world(|world| world.entities.iter().map(|(id, en)| (*id, en.get_pos())).collect())
+}
+
+#[test]
+fn save_round_trip() {
+    let game = TestGame::playing(20);
+    game.ticks(30);
+
+    game.world(|world| {
+        let data = save::save_world(world);
+        let loaded = save::load_world(&data, channel().0).expect("Can't load");
+
+        assert!(loaded.blocks == world.blocks);
+        assert_eq!(loaded.seed, world.seed);
+        assert_eq!(loaded.rng, world.rng);
+        assert_eq!(loaded.stats, world.stats);
+        assert_eq!(
+            loaded.entities.iter().map(|(id, en)| (*id, en.get_pos(), en.get_name())).collect::<Vec<_>>(),
+            world.entities.iter().map(|(id, en)| (*id, en.get_pos(), en.get_name())).collect::<Vec<_>>());
+    });
+}
+
+#[test]
+fn loading_a_save_with_too_many_items() {
+    let game = TestGame::playing(22);
+    game.world(|world| {
+        // Older saves had no limit on the inventory
+        let mut inventory = Inventory::new(100);
+        for _ in 0..PLAYER_SLOTS + 2 {
+            inventory.add(InventoryItem::Block(block::STONE.clone()), 99);
+        }
+        inventory.add(InventoryItem::Bullet, 5);
+        inventory.active = inventory.slots().len() - 1;
+        let id = world.get_player_id().unwrap();
+        let pos = world.entities[&id].get_pos();
+        if let Some(EntityWrapper::WPlayer(player)) = world.entities.get_mut(&id) {
+            player.inventory = inventory;
+        }
+
+        let loaded = save::load_world(&save::save_world(world), channel().0).expect("Can't load");
+        let player = match loaded.entities.get(&id) {
+            Some(EntityWrapper::WPlayer(player)) => player,
+            _ => panic!("No player"),
+        };
+        assert_eq!(player.inventory.slots().len(), PLAYER_SLOTS);
+        assert!(player.inventory.active < PLAYER_SLOTS);
+
+        let dropped: Vec<(InventoryItem, u64)> = loaded.entities_at(pos).iter()
+            .filter_map(|id| match loaded.entities.get(id) {
+                Some(EntityWrapper::WItemDrop(drop)) => Some((drop.item.clone(), drop.count)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(dropped, vec![(InventoryItem::Block(block::STONE.clone()), 2 * 99), (InventoryItem::Bullet, 5)]);
+    });
+}
+
+#[test]
+fn broken_saves_are_rejected() {
+    let game = TestGame::playing(21);
+    game.world(|world| {
+        let data = save::save_world(world);
+        assert_eq!(save::load_world(&data[..data.len() / 2], channel().0).err(), Some(save::LoadError::UnexpectedEnd));
+        assert_eq!(save::load_world(b"nope", channel().0).err(), Some(save::LoadError::NotASave));
+    });
+}
+
+#[test]
+fn continuing_a_saved_game() {
+    let game = TestGame::playing(22);
+    game.ticks(10);
+    let positions = entity_positions(&game);
+
+    assert!(soviet_sim::save_game() > 0);
+    game.press(letter('r'));
+    game.press(letter('r'));
+    game.ticks(2);
+    assert!(!game.is_playing());
+
+    // Dying forgets the save
+    assert_eq!(soviet_sim::save_game(), 0);
+
+    drop(game);
+    let game = TestGame::playing(22);
+    game.ticks(10);
+    soviet_sim::save_game();
+    soviet_sim::start(SIZE.0, SIZE.1);
+    game.press(letter('c'));
+    assert_eq!(entity_positions(&game), positions);
+}
+
+#[test]
+fn replaying_a_run() {
+    let game = TestGame::playing(23);
+    for &dir in &[UP, LEFT, DOWN, RIGHT, RIGHT, UP] {
+        game.press(dir);
+        game.ticks(5);
+    }
+    game.press_with(letter('m'), LEFT);
+    game.ticks(40);
+    // One tick in TestGame::playing
+    let frames = 1 + 6 * 5 + 40;
+    let positions = entity_positions(&game);
+
+    let len = soviet_sim::export_recording();
+    let recording = unsafe { std::slice::from_raw_parts(soviet_sim::buffer_ptr(), len).to_vec() };
+
+    let ptr = soviet_sim::alloc_buffer(len);
+    unsafe { std::ptr::copy_nonoverlapping(recording.as_ptr(), ptr, len); }
+    assert!(soviet_sim::start_replay(len));
+
+    // Pressing keys does nothing during a replay
+    game.press(LEFT);
+    game.ticks(frames);
+    assert_eq!(entity_positions(&game), positions);
+}
+
+#[test]
+fn pausing_a_run() {
+    let game = TestGame::playing(24);
+    game.press(UP);
+    game.ticks(5);
+    game.press(ESCAPE);
+    let positions = entity_positions(&game);
+    game.ticks(50);
+    assert!(game.screen_contains("PAUSED"));
+    assert_eq!(entity_positions(&game), positions);
+
+    game.press(ESCAPE);
+    game.ticks(5);
+    assert!(!game.screen_contains("PAUSED"));
+    let positions = entity_positions(&game);
+
+    // The paused frames aren't part of the recording
+    let len = soviet_sim::export_recording();
+    let recording = unsafe { std::slice::from_raw_parts(soviet_sim::buffer_ptr(), len).to_vec() };
+    let ptr = soviet_sim::alloc_buffer(len);
+    unsafe { std::ptr::copy_nonoverlapping(recording.as_ptr(), ptr, len); }
+    assert!(soviet_sim::start_replay(len));
+    game.ticks(1 + 5 + 5);
+    assert_eq!(entity_positions(&game), positions);
+}
+
+#[test]
+fn pause_menu() {
+    let game = TestGame::playing(25);
+    let start = entity_positions(&game);
+    game.ticks(10);
+
+    // Restart with the same seed
+    game.press(ESCAPE);
+    game.press(DOWN);
+    game.press(ENTER);
+    game.press(ENTER);
+    game.tick();
+    assert_eq!(entity_positions(&game), start);
+
+    // Save, then quit to the menu and continue from there
+    game.press(ESCAPE);
+    game.press(DOWN);
+    game.press(DOWN);
+    game.press(ENTER);
+    game.tick();
+    assert!(game.screen_contains("Saved"));
+    game.press(DOWN);
+    game.press(DOWN);
+    game.press(ENTER);
+    game.tick();
+    assert!(!game.is_playing());
+    assert!(game.screen_contains("COMMUNISM SIMULATOR"));
+
+    game.press(letter('c'));
+    assert_eq!(entity_positions(&game), start);
+}
+
+#[test]
+fn high_scores() {
+    let game = TestGame::playing(9);
+    game.ticks(59);
+    game.press(letter('r'));
+    game.press(letter('r'));
+    game.ticks(2);
+    assert!(game.screen_contains("New best run!"));
+    assert!(game.screen_contains("1. Lasted 0:01, seed 9"));
+
+    game.press(ENTER);
+    game.tick();
+    assert!(game.screen_contains("Best on 2014 (Easy)"));
+    assert!(game.screen_contains("1. Lasted 0:01, seed 9"));
+
+    // Watching the run again doesn't add it twice
+    let len = soviet_sim::export_recording();
+    assert!(len > 0);
+    assert!(soviet_sim::start_replay(len));
+    game.ticks(64);
+    assert!(game.screen_contains("Best runs"));
+    assert_eq!(highscores::scores(Difficulty::EASY).len(), 1);
+
+    // Wins come first, then the losses that lasted longest
+    let win = Score { won: true, ticks: 5000, seed: 1 };
+    let short = Score { won: false, ticks: 10, seed: 2 };
+    assert_eq!(highscores::add(Difficulty::EASY, win), Some(0));
+    assert_eq!(highscores::add(Difficulty::EASY, short), Some(2));
+    assert_eq!(highscores::scores(Difficulty::EASY)[1].seed, 9);
+    assert!(highscores::scores(Difficulty::MEDIUM).is_empty());
+
+    for _ in 0..highscores::MAX_SCORES {
+        highscores::add(Difficulty::EASY, win);
+    }
+    assert_eq!(highscores::scores(Difficulty::EASY).len(), highscores::MAX_SCORES);
+    assert_eq!(highscores::add(Difficulty::EASY, short), None);
+}
+
+#[test]
+fn simulations_are_deterministic() {
+    let run = || {
+        let mut sim = Simulation::with_size(Difficulty::HARD, 1917, 60, 40);
+        sim.step(100);
+        sim.apply(&Action::Move(MoveDir::Left));
+        sim.step(400);
+        sim.world.entities.clone()
+    };
+    assert!(run() == run());
+}
+
+#[test]
+fn simulations_end() {
+    let mut sim = Simulation::with_size(Difficulty::EASY, 3, 60, 40);
+    sim.apply(&Action::Die);
+    assert_eq!(sim.step(10), Some(soviet_sim::sim::Outcome::Died));
+    assert_eq!(sim.ticks(), 0);
+}
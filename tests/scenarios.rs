@@ -0,0 +1,370 @@
+// Things the player can do in a run, checked against the world state
+
+extern crate soviet_sim;
+#[macro_use]
+extern crate lazy_static;
+
+mod common;
+
+use common::*;
+use soviet_sim::block::{self, Block};
+use soviet_sim::crafting::{Recipe, Missing};
+use soviet_sim::entity::{Entity, EntityWrapper, Josef, Player, Police, ItemDrop};
+use soviet_sim::world::UNDO_ENEMY_DIST;
+use soviet_sim::move_dir::MoveDir;
+use soviet_sim::inventory::{InventoryItem, Inventory, PICKAXE, SUPER_BOOTS, PLAYER_SLOTS};
+
+/// Put a block right of the player and return its position
+fn block_right_of_player(game: &TestGame, block: &Block) -> (u16, u16) {
+    let (x, y) = game.player_pos();
+    game.world(|world| world.blocks[x as usize + 1][y as usize] = block.clone());
+    (x + 1, y)
+}
+
+fn count_of(game: &TestGame, item: &InventoryItem) -> u64 {
+    game.player(|player| player.inventory.count(item))
+}
+
+#[test]
+fn crafting_a_bomb() {
+    let game = TestGame::playing(1);
+    game.player(|player| {
+        player.inventory = Inventory::with_items(PLAYER_SLOTS, vec![
+            (InventoryItem::Bullet, 7),
+            (InventoryItem::Block(block::STONE.clone()), 12),
+        ]);
+    });
+
+    game.press(letter('i'));
+    game.tick();
+    assert!(game.screen_contains("INVENTORY"));
+
+    // The bomb is the fourth recipe, and needs a workbench
+    for _ in 0..3 {
+        game.press(DOWN);
+    }
+    game.press(ENTER);
+    assert_eq!(count_of(&game, &InventoryItem::Bomb), 0);
+
+    block_right_of_player(&game, &block::by_id("workbench").unwrap());
+    game.press(ENTER);
+
+    assert_eq!(count_of(&game, &InventoryItem::Bomb), 1);
+    assert_eq!(count_of(&game, &InventoryItem::Bullet), 0);
+    assert_eq!(count_of(&game, &InventoryItem::Block(block::STONE.clone())), 2);
+
+    // Not enough for another one
+    game.press(ENTER);
+    assert_eq!(count_of(&game, &InventoryItem::Bomb), 1);
+}
+
+#[test]
+fn crafting_several_at_once() {
+    let game = TestGame::playing(1);
+    game.player(|player| player.inventory = Inventory::with_items(PLAYER_SLOTS, vec![(InventoryItem::Block(block::WALL.clone()), 23)]));
+
+    // Bullets are the third recipe
+    game.press(letter('i'));
+    game.press(DOWN);
+    game.press(DOWN);
+    game.tick();
+    assert!(game.screen_contains("Craft < 1 >  (enough for 4)"));
+    assert!(game.screen_contains("23/5 Wall"));
+
+    game.press(RIGHT);
+    game.press(RIGHT);
+    game.tick();
+    assert!(game.screen_contains("23/15 Wall"));
+    game.press(ENTER);
+    assert_eq!(count_of(&game, &InventoryItem::Bullet), 3);
+
+    // Only one more fits
+    game.press(ENTER);
+    assert_eq!(count_of(&game, &InventoryItem::Bullet), 4);
+    game.tick();
+    assert!(game.screen_contains("Crafted 1, then needed 2 more Wall"));
+
+    game.player(|player| player.inventory.add(InventoryItem::Block(block::WALL.clone()), 12));
+    game.press_with(SHIFT, ENTER);
+    assert_eq!(count_of(&game, &InventoryItem::Bullet), 7);
+    assert_eq!(count_of(&game, &InventoryItem::Block(block::WALL.clone())), 0);
+}
+
+#[test]
+fn failed_crafts_say_what_is_missing() {
+    let game = TestGame::playing(1);
+    game.player(|player| player.inventory = Inventory::with_items(PLAYER_SLOTS, vec![(InventoryItem::Bullet, 3)]));
+
+    // The bomb needs a workbench
+    game.press(letter('i'));
+    for _ in 0..3 {
+        game.press(DOWN);
+    }
+    game.press(ENTER);
+    game.tick();
+    assert!(game.screen_contains("Need 4 more Bullet, 10 more Stone, a Workbench next to you"));
+    assert_eq!(count_of(&game, &InventoryItem::Bullet), 3);
+}
+
+#[test]
+fn uncraftable_recipes_are_greyed_out() {
+    let game = TestGame::playing(1);
+    game.player(|player| player.inventory = Inventory::with_items(PLAYER_SLOTS, vec![(InventoryItem::Block(block::WALL.clone()), 10)]));
+
+    game.press(letter('i'));
+    game.tick();
+    let workbench = game.text_color("Workbench").expect("No workbench recipe");
+    let forge = game.text_color("Forge").expect("No forge recipe");
+    assert!(workbench.0 > forge.0);
+
+    // A workbench next to the player makes the forge craftable, if there's stone too
+    block_right_of_player(&game, &block::by_id("workbench").unwrap());
+    game.player(|player| player.inventory.add(InventoryItem::Block(block::STONE.clone()), 20));
+    game.tick();
+    assert!(game.text_color("Forge").unwrap().0 > forge.0);
+}
+
+#[test]
+fn crafting_with_tools_and_stations() {
+    let game = TestGame::playing(1);
+    let recipe = Recipe {
+        id: "rubble".into(),
+        out: vec![(InventoryItem::Block(block::STONE.clone()), 3), (InventoryItem::Bullet, 1)],
+        needed: vec![(InventoryItem::Block(block::WALL.clone()), 1)],
+        tools: vec![PICKAXE],
+        station: Some(block::MOVER.clone()),
+    };
+    let craft = || game.world(|world| {
+        let id = world.get_player_id().unwrap();
+        Player::craft(world, id, &recipe)
+    });
+    game.player(|player| player.inventory = Inventory::with_items(PLAYER_SLOTS, vec![(InventoryItem::Block(block::WALL.clone()), 2)]));
+
+    // No pickaxe
+    block_right_of_player(&game, &block::MOVER);
+    assert_eq!(craft(), Err(vec![Missing::Tool(PICKAXE)]));
+
+    // A worn pickaxe still works, and isn't used up
+    game.player(|player| player.inventory.add(InventoryItem::Pickaxe(3, 1001), 1));
+    assert_eq!(craft(), Ok(()));
+    assert_eq!(count_of(&game, &InventoryItem::Block(block::STONE.clone())), 3);
+    assert_eq!(count_of(&game, &InventoryItem::Bullet), 1);
+    assert_eq!(count_of(&game, &InventoryItem::Block(block::WALL.clone())), 1);
+    assert_eq!(count_of(&game, &InventoryItem::Pickaxe(3, 1001)), 1);
+
+    // Away from the mover
+    block_right_of_player(&game, &block::GROUND);
+    assert_eq!(craft(), Err(vec![Missing::Station(block::MOVER.clone())]));
+}
+
+#[test]
+fn mining_a_wall() {
+    let game = TestGame::playing(2);
+    let start = game.player_pos();
+    let wall_pos = block_right_of_player(&game, &block::WALL);
+
+    game.press_with(letter('m'), RIGHT);
+
+    assert_eq!(game.player_pos(), wall_pos);
+    game.world(|world| assert!(world.blocks[wall_pos.0 as usize][wall_pos.1 as usize] == *block::GROUND));
+    assert_eq!(count_of(&game, &InventoryItem::Block(block::WALL.clone())), 1);
+
+    // Ground can't be mined
+    game.press_with(letter('m'), LEFT);
+    assert_eq!(game.player_pos(), wall_pos);
+    assert!(start != wall_pos);
+}
+
+#[test]
+fn undoing_blocks() {
+    let game = TestGame::playing(2);
+    let start = game.player_pos();
+    let stone = InventoryItem::Block(block::STONE.clone());
+    // No enemies around to get in the way
+    game.world(|world| for id in world.entities_within(start, UNDO_ENEMY_DIST * 2) {
+        match world.entities[&id] {
+            EntityWrapper::WPolice(_) | EntityWrapper::WJosef(_) => { world.remove_entity(id); }
+            _ => {}
+        }
+    });
+
+    // Breaking is undone by stepping back and putting the block back
+    let wall_pos = block_right_of_player(&game, &block::WALL);
+    game.press_with(letter('m'), RIGHT);
+    assert_eq!(game.player_pos(), wall_pos);
+    game.press(letter('z'));
+    assert_eq!(game.player_pos(), start);
+    game.world(|world| assert!(world.blocks[wall_pos.0 as usize][wall_pos.1 as usize] == *block::WALL));
+    assert_eq!(count_of(&game, &InventoryItem::Block(block::WALL.clone())), 0);
+
+    // Placing gives the block back
+    let left = (start.0 - 1, start.1);
+    game.world(|world| world.blocks[left.0 as usize][left.1 as usize] = block::GROUND.clone());
+    game.player(|player| player.inventory = Inventory::with_items(PLAYER_SLOTS, vec![(stone.clone(), 1)]));
+    game.press_with(letter('p'), LEFT);
+    assert_eq!(count_of(&game, &stone), 0);
+    game.press(letter('z'));
+    game.world(|world| assert!(world.blocks[left.0 as usize][left.1 as usize] == *block::GROUND));
+    assert_eq!(count_of(&game, &stone), 1);
+
+    game.press(letter('z'));
+    game.tick();
+    assert!(game.screen_contains("Nothing to undo"));
+
+    // Not with the police next to you
+    game.press_with(letter('p'), LEFT);
+    game.world(|world| world.add_entity(EntityWrapper::WPolice(Police::new((start.0, start.1 + 2), 60, 60))));
+    game.press(letter('z'));
+    game.tick();
+    assert!(game.screen_contains("Can't undo with enemies nearby"));
+    assert_eq!(count_of(&game, &stone), 0);
+}
+
+#[test]
+fn walking_into_a_wall() {
+    let game = TestGame::playing(3);
+    let start = game.player_pos();
+    block_right_of_player(&game, &block::WALL);
+
+    game.press(RIGHT);
+
+    assert_eq!(game.player_pos(), start);
+}
+
+#[test]
+fn running_across_a_drop() {
+    let game = TestGame::playing(3);
+    let (x, y) = game.player_pos();
+    let drop_pos = (x + 2, y);
+    game.world(|world| {
+        for i in 1..6 {
+            world.blocks[(x + i) as usize][y as usize] = block::GROUND.clone();
+        }
+        world.add_entity(EntityWrapper::WItemDrop(ItemDrop::new(drop_pos, InventoryItem::Bullet, 3)));
+        assert!(world.is_occupied(drop_pos));
+        assert!(!world.is_blocked(drop_pos));
+    });
+    let bullets = count_of(&game, &InventoryItem::Bullet);
+    // Running takes boots
+    game.player(|player| player.inventory.add(SUPER_BOOTS, 1));
+
+    soviet_sim::key_down(SHIFT);
+    game.press_with(CTRL, RIGHT);
+    soviet_sim::key_up(SHIFT);
+    assert_eq!(game.world(|world| world.auto_walk[..3].to_vec()), vec![MoveDir::Right; 3]);
+
+    game.ticks(60);
+    assert!(game.player_pos().0 > drop_pos.0);
+    assert_eq!(count_of(&game, &InventoryItem::Bullet), bullets + 3);
+}
+
+#[test]
+fn stepping_on_a_mover() {
+    let game = TestGame::playing(4);
+    let mover_pos = block_right_of_player(&game, &block::MOVER);
+
+    game.press(RIGHT);
+
+    let pos = game.player_pos();
+    assert!(pos != mover_pos);
+    game.world(|world| assert!(world.blocks[pos.0 as usize][pos.1 as usize].is_passable()));
+}
+
+#[test]
+fn stepping_on_communism() {
+    let game = TestGame::playing(5);
+    let hunger = game.player(|player| player.hunger);
+    let communism_pos = block_right_of_player(&game, &block::COMMUNISM);
+
+    game.press(RIGHT);
+
+    assert_eq!(game.player_pos(), communism_pos);
+    assert_eq!(game.player(|player| player.hunger), hunger + 1);
+    game.world(|world| assert!(world.blocks[communism_pos.0 as usize][communism_pos.1 as usize] == *block::GROUND));
+}
+
+#[test]
+fn killing_josef() {
+    let game = TestGame::playing(6);
+
+    let (josef_id, health) = game.world(|world|
+        world.entities.iter()
+            .filter_map(|(id, en)| if let EntityWrapper::WJosef(j) = en { Some((*id, j.health)) } else { None })
+            .next()
+            .expect("No Josef"));
+
+    // Hurting him a bit isn't enough
+    game.world(|world| Josef::hurt(world, josef_id, 1));
+    game.tick();
+    assert!(game.is_playing());
+
+    game.world(|world| Josef::hurt(world, josef_id, health + 1));
+    game.ticks(2);
+    assert!(!game.is_playing());
+    // Debug builds draw their marker over the start of the message
+    assert!(game.screen_contains("game over"));
+    assert!(!game.screen_contains("lol!"));
+}
+
+#[test]
+fn dying() {
+    let game = TestGame::playing(7);
+    game.press(letter('r'));
+    game.tick();
+    assert!(game.is_playing());
+    assert!(game.screen_contains("Press R again to die"));
+    game.press(letter('r'));
+    game.ticks(2);
+
+    assert!(!game.is_playing());
+    assert!(game.screen_contains("lol!"));
+    assert!(game.screen_contains("seed 7"));
+
+    game.press(ENTER);
+    game.tick();
+    assert!(game.screen_contains("COMMUNISM SIMULATOR"));
+}
+
+#[test]
+fn holding_the_die_key() {
+    let game = TestGame::playing(7);
+    soviet_sim::key_down(letter('r'));
+    game.tick();
+    soviet_sim::key_down(letter('r'));
+    game.ticks(2);
+    assert!(game.is_playing());
+
+    soviet_sim::key_up(letter('r'));
+    game.press(letter('r'));
+    game.ticks(2);
+    assert!(!game.is_playing());
+}
+
+#[test]
+fn stats_of_a_run() {
+    let game = TestGame::playing(9);
+    block_right_of_player(&game, &block::WALL);
+    game.press_with(letter('m'), RIGHT);
+    game.world(|world| {
+        let id = world.get_player_id().unwrap();
+        world.hurt(id, 2, "Police");
+        world.hurt(id, 1, "Bomb");
+    });
+    // One tick in TestGame::playing and one as the run ends
+    game.ticks(60 * 2 - 2);
+    game.press(letter('r'));
+    game.press(letter('r'));
+    game.ticks(2);
+
+    assert!(game.screen_contains("Survived 0:02 (120 ticks)"));
+    assert!(game.screen_contains("Blocks mined: 1"));
+    assert!(game.screen_contains("Damage taken: 3"));
+    assert!(game.screen_contains("2 from Police"));
+
+    let len = soviet_sim::run_stats();
+    let text = unsafe { String::from_utf8_lossy(std::slice::from_raw_parts(soviet_sim::buffer_ptr(), len)).into_owned() };
+    assert!(text.contains("blocks_mined = 1\n"));
+    assert!(text.contains("damage_from_police = 2\n"));
+    assert!(text.contains("damage_from_bomb = 1\n"));
+    assert!(text.contains("josef_health = "));
+}
@@ -0,0 +1,53 @@
+// What ends up on the screen
+
+extern crate soviet_sim;
+#[macro_use]
+extern crate lazy_static;
+
+mod common;
+
+use common::*;
+
+#[test]
+fn menu() {
+    let game = TestGame::new();
+    game.tick();
+
+    assert!(game.screen_contains("COMMUNISM SIMULATOR"));
+    assert!(game.screen_contains("2014 (Easy)"));
+
+    game.press(RIGHT);
+    game.tick();
+    assert!(game.screen_contains("1989 (Medium)"));
+}
+
+#[test]
+fn typing_a_seed() {
+    let game = TestGame::new();
+    game.type_number(1234);
+    game.press(8); // Backspace
+    game.tick();
+
+    assert!(game.screen_contains("Seed: 123 "));
+}
+
+#[test]
+fn player_is_drawn() {
+    let game = TestGame::playing(10);
+    game.tick();
+
+    let (x, y) = game.player_pos();
+    let scroll = game.world(|world| world.scroll);
+    let screen_pos = ((x as i16 - scroll.0) as u16, (y as i16 - scroll.1) as u16);
+
+    assert_eq!(game.host.char_at(screen_pos), '@');
+}
+
+#[test]
+fn hotbar_shows_inventory() {
+    let game = TestGame::playing(11);
+    game.player(|player| player.inventory.add(soviet_sim::inventory::InventoryItem::Bullet, 42));
+    game.tick();
+
+    assert!(game.host.row(SIZE.1 - 2).contains("x42"));
+}
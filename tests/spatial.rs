@@ -0,0 +1,55 @@
+// The position index has to keep up with everything entities do
+
+extern crate soviet_sim;
+
+use soviet_sim::sim::Simulation;
+use soviet_sim::difficulty::Difficulty;
+use soviet_sim::controls::Action;
+use soviet_sim::move_dir::MoveDir;
+use soviet_sim::entity::{EntityWrapper, Bomb, Bullet};
+
+fn check_index(sim: &Simulation) {
+    let world = &sim.world;
+    for (id, en) in &world.entities {
+        assert!(world.entities_at(en.get_pos()).contains(id));
+    }
+    let all: Vec<u64> = world.entities.keys().cloned().collect();
+    assert_eq!(world.entities_in_rect((0, 0), (u16::max_value(), u16::max_value())), all);
+}
+
+#[test]
+fn index_follows_entities() {
+    let mut sim = Simulation::with_size(Difficulty::REALITY, 1991, 60, 40);
+    let player = sim.player().unwrap().pos;
+
+    sim.world.add_entity(EntityWrapper::WBomb(Bomb::new(MoveDir::Up.move_vec(player), 20)));
+    sim.world.add_entity(EntityWrapper::WBullet(Bullet::new(MoveDir::Left.move_vec(player), MoveDir::Left)));
+    check_index(&sim);
+
+    for &dir in &[MoveDir::Left, MoveDir::Up, MoveDir::Right, MoveDir::Down] {
+        sim.apply(&Action::Move(dir));
+        sim.step(50);
+        check_index(&sim);
+    }
+}
+
+#[test]
+fn queries() {
+    let mut sim = Simulation::with_size(Difficulty::EASY, 4, 60, 40);
+    let pos = sim.player().unwrap().pos;
+    let id = sim.world.get_player_id().unwrap();
+
+    assert_eq!(sim.world.entities_at(pos), vec![id]);
+    assert!(sim.world.entities_within(pos, 1).contains(&id));
+    assert!(sim.world.entities_in_rect(pos, pos).contains(&id));
+
+    let away = (pos.0 + 3, pos.1);
+    sim.world.set_entity_pos(id, away);
+    assert!(sim.world.entities_at(pos).is_empty());
+    assert!(!sim.world.entities_within(pos, 3).contains(&id));
+    assert!(sim.world.entities_within(pos, 4).contains(&id));
+    assert!(sim.world.entities_in_rect((pos.0, pos.1), (pos.0 + 3, pos.1)).contains(&id));
+
+    sim.world.remove_entity(id);
+    assert!(!sim.world.is_occupied(away));
+}